@@ -2,6 +2,7 @@ import express, { Request, Response } from "express";
 import cors from "cors";
 
 import { getSignatureOracleRequest, getStableCoinPublicKey } from "./utils/oracle_requester";
+import { isSubscriptionSuspended } from "./utils/subscription";
 import { PriceMessage } from "./utils/types";
 
 
@@ -27,7 +28,15 @@ app.get("/example/getPrice", async (req: Request, res: Response<PriceMessage>, n
         //potentially counterfactual transaction so that a user is not bleeding out 
         //the API. This is up to the DAPP developer.
         const nftId = process.env.STABLECOIN_NFT_ID
+        // Suspended subscriptions are refused on-chain, don't spend a nonce on them
+        if (await isSubscriptionSuspended(nftId)) {
+            throw new HttpError(403, `Subscription ${nftId} is suspended`);
+        }
+
         const currentNonceResponse = await fetch(process.env.ORACLE_BACKEND_URL+"/nonce/" + nftId)
+        if (!currentNonceResponse.ok) {
+            throw new Error(`Could not get the nonce of subscription ${nftId} (status ${currentNonceResponse.status})`);
+        }
 
         const nonceJson = await currentNonceResponse.json(); // {nonce: "123"};
 
@@ -51,8 +60,15 @@ app.get("/example/getTmpPrice", async (req: Request, res: Response<PriceMessage>
     //Or get your own feed from Gateio/Binance as a temporary price indicator
 })
 
+class HttpError extends Error {
+    constructor(public status: number, message: string) {
+        super(message);
+    }
+}
+
 function errorHandler(err, req, res, next) {
-    res.status(500).json({ error: err.message, status: 500 })
+    const status = err instanceof HttpError ? err.status : 500;
+    res.status(status).json({ error: err.message, status })
 }
 app.use(errorHandler)
 
@@ -0,0 +1,23 @@
+import {
+  GatewayApiClient,
+  ProgrammaticScryptoSborValueBool,
+  ProgrammaticScryptoSborValueTuple,
+} from "@radixdlt/babylon-gateway-api-sdk";
+
+const gatewayApi = GatewayApiClient.initialize({
+  networkId: Number(process.env.NETWORK_ID),
+  applicationName: "Gumball Dapp Backend",
+});
+
+// Reads the `suspended` field of the OracleSubscription NFT from the ledger
+export async function isSubscriptionSuspended(nftId: string): Promise<boolean> {
+  const subscription = await gatewayApi.state.getNonFungibleData(
+    process.env.VITE_ORACLE_SUBSCRIPTION_RESOURCE_ADDRESS,
+    nftId
+  );
+
+  const fields = (subscription.data?.programmatic_json as ProgrammaticScryptoSborValueTuple)?.fields ?? [];
+  const suspended = fields.find((field) => field.field_name === "suspended") as ProgrammaticScryptoSborValueBool;
+
+  return suspended?.value === true;
+}
@@ -4,6 +4,10 @@ export type PriceMessage = {
     price: number;
     nonce: string;
     createdAt: number;
+    bid: number;
+    ask: number;
+    confidence: number;
+    validUntil: number;
     signature?: string;
 }
 
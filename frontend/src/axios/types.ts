@@ -16,5 +16,9 @@ export type PriceMessage = {
     price: number;
     nonce: string;
     createdAt: number;
+    bid: number;
+    ask: number;
+    confidence: number;
+    validUntil: number;
     signature?: string;
 }
\ No newline at end of file
@@ -1,5 +1,5 @@
 import type { PriceMessage } from "@/axios/types";
 
 export function priceMsgToString(msg: PriceMessage): string {
-    return msg.marketId+"-"+msg.price+"-"+msg.nonce+"-"+msg.createdAt;
+    return msg.marketId+"-"+msg.price+"-"+msg.nonce+"-"+msg.createdAt+"-"+msg.bid+"-"+msg.ask+"-"+msg.confidence+"-"+msg.validUntil;
 }
\ No newline at end of file
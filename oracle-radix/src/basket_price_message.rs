@@ -0,0 +1,146 @@
+use std::str::FromStr;
+
+use scrypto::prelude::*;
+
+use crate::oracle_error::OracleError;
+
+/// Prices of several markets signed at the same instant under a single nonce.
+#[derive(ScryptoSbor, Clone)]
+pub struct BasketPriceMessage {
+    pub prices: Vec<(String, Decimal)>,
+    pub nonce: u64,
+    pub created_at: u64,
+    pub valid_until: u64,
+}
+
+impl BasketPriceMessage {
+    pub fn to_string(&self) -> String {
+        let prices: Vec<String> = self
+            .prices
+            .iter()
+            .map(|(market_id, price)| format!("{}={}", market_id, price))
+            .collect();
+
+        return format!(
+            "{}-{}-{}-{}",
+            prices.join(","),
+            self.nonce,
+            self.created_at,
+            self.valid_until
+        );
+    }
+}
+
+impl FromStr for BasketPriceMessage {
+    type Err = OracleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split("-").collect();
+
+        if parts.len() != 4 {
+            Err(OracleError::InvalidMessageFormat)
+        } else {
+            let mut prices: Vec<(String, Decimal)> = Vec::new();
+
+            for entry in parts.first().unwrap().split(",") {
+                let (market_id, price) = entry
+                    .split_once("=")
+                    .ok_or(OracleError::InvalidBasketEntry)?;
+
+                if market_id.is_empty() {
+                    return Err(OracleError::InvalidMarketId);
+                }
+
+                if prices.iter().any(|(other_market_id, _)| other_market_id == market_id) {
+                    return Err(OracleError::DuplicateBasketMarket);
+                }
+
+                let price = Decimal::from_str(price)
+                    .map_err(|_| OracleError::InvalidPrice)?;
+
+                if price <= Decimal::ZERO {
+                    return Err(OracleError::NonPositiveBasketPrice);
+                }
+
+                prices.push((market_id.to_string(), price));
+            }
+
+            let nonce = parts
+                .get(1)
+                .unwrap()
+                .parse()
+                .map_err(|_| OracleError::InvalidNonce)?;
+
+            let created_at = parts
+                .get(2)
+                .unwrap()
+                .parse()
+                .map_err(|_| OracleError::InvalidCreationDate)?;
+
+            let valid_until = parts
+                .get(3)
+                .unwrap()
+                .parse()
+                .map_err(|_| OracleError::InvalidValidUntil)?;
+
+            if valid_until < created_at {
+                return Err(OracleError::ExpiresBeforeCreation);
+            }
+
+            Ok(BasketPriceMessage {
+                prices,
+                nonce,
+                created_at,
+                valid_until,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod basket_price_message_tests {
+    use scrypto::prelude::*;
+
+    use crate::basket_price_message::BasketPriceMessage;
+
+    #[test]
+    pub fn test_to_string() {
+        let basket_price_message = BasketPriceMessage {
+            prices: vec![
+                ("GATEIO:XRD_USDT".to_string(), dec!(0.0123)),
+                ("BINANCE:BTC_USDT".to_string(), dec!(64000.5)),
+            ],
+            nonce: 3,
+            created_at: 1230,
+            valid_until: 1290,
+        };
+
+        assert_eq!(
+            basket_price_message.to_string(),
+            "GATEIO:XRD_USDT=0.0123,BINANCE:BTC_USDT=64000.5-3-1230-1290"
+        );
+    }
+
+    #[test]
+    pub fn from_string_test() {
+        let basket_price_message = BasketPriceMessage::from_str(
+            "GATEIO:XRD_USDT=0.0123,BINANCE:BTC_USDT=64000.5-3-1230-1290",
+        )
+        .unwrap();
+        assert!(
+            basket_price_message.prices
+                == vec![
+                    ("GATEIO:XRD_USDT".to_string(), dec!(0.0123)),
+                    ("BINANCE:BTC_USDT".to_string(), dec!(64000.5)),
+                ]
+                && basket_price_message.nonce == 3
+                && basket_price_message.created_at == 1230
+                && basket_price_message.valid_until == 1290
+        );
+
+        assert!(BasketPriceMessage::from_str("GATEIO:XRD_USDT=0.0123-3-1230").is_err());
+        assert!(BasketPriceMessage::from_str("GATEIO:XRD_USDT-3-1230-1290").is_err());
+        assert!(BasketPriceMessage::from_str("A=1,A=2-3-1230-1290").is_err());
+        assert!(BasketPriceMessage::from_str("A=1,B=0-3-1230-1290").is_err())
+    }
+}
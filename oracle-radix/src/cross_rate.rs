@@ -0,0 +1,173 @@
+//! Derivation of synthetic price messages from verified ones, e.g. XRD/EUR out of XRD/USDT and
+//! USDT/EUR.
+//!
+//! Derived messages are not signed by the oracle: their nonce is `DERIVED_NONCE` and they must only
+//! be built from messages which went through the oracle checks. They take the older `created_at` and
+//! the earlier `valid_until` of their inputs.
+
+use std::cmp::min;
+
+use scrypto::prelude::*;
+
+use crate::oracle_error::OracleError;
+use crate::price_message::PriceMessage;
+
+/// Nonce of derived messages, the oracle never signs a message with this nonce.
+pub const DERIVED_NONCE: u64 = u64::MAX;
+
+/// Derives `a * b`, e.g. XRD/USDT * USDT/EUR = XRD/EUR.
+pub fn multiply(a: &PriceMessage, b: &PriceMessage) -> Result<PriceMessage, OracleError> {
+    check_positive(a)?;
+    check_positive(b)?;
+
+    Ok(PriceMessage {
+        market_id: format!("{}*{}", a.market_id, b.market_id),
+        price: mul(a.price, b.price)?,
+        nonce: DERIVED_NONCE,
+        created_at: min(a.created_at, b.created_at),
+        bid: mul(a.bid, b.bid)?,
+        ask: mul(a.ask, b.ask)?,
+        confidence: add(mul(a.confidence, b.price)?, mul(b.confidence, a.price)?)?,
+        valid_until: min(a.valid_until, b.valid_until),
+    })
+}
+
+/// Derives `a / b`, e.g. XRD/USD / EUR/USD = XRD/EUR.
+pub fn divide(a: &PriceMessage, b: &PriceMessage) -> Result<PriceMessage, OracleError> {
+    check_positive(a)?;
+    check_positive(b)?;
+
+    let price = div(a.price, b.price)?;
+
+    Ok(PriceMessage {
+        market_id: format!("{}/{}", a.market_id, b.market_id),
+        price,
+        nonce: DERIVED_NONCE,
+        created_at: min(a.created_at, b.created_at),
+        bid: div(a.bid, b.ask)?,
+        ask: div(a.ask, b.bid)?,
+        confidence: div(add(a.confidence, mul(price, b.confidence)?)?, b.price)?,
+        valid_until: min(a.valid_until, b.valid_until),
+    })
+}
+
+/// Derives `1 / a`, e.g. USDT/XRD out of XRD/USDT.
+pub fn invert(a: &PriceMessage) -> Result<PriceMessage, OracleError> {
+    check_positive(a)?;
+
+    Ok(PriceMessage {
+        market_id: format!("1/{}", a.market_id),
+        price: div(Decimal::ONE, a.price)?,
+        nonce: DERIVED_NONCE,
+        created_at: a.created_at,
+        bid: div(Decimal::ONE, a.ask)?,
+        ask: div(Decimal::ONE, a.bid)?,
+        confidence: div(a.confidence, mul(a.price, a.price)?)?,
+        valid_until: a.valid_until,
+    })
+}
+
+fn check_positive(price_message: &PriceMessage) -> Result<(), OracleError> {
+    if price_message.price.is_positive()
+        && price_message.bid.is_positive()
+        && price_message.ask.is_positive()
+    {
+        Ok(())
+    } else {
+        Err(OracleError::NonPositiveCrossRatePrice)
+    }
+}
+
+fn add(a: Decimal, b: Decimal) -> Result<Decimal, OracleError> {
+    a.checked_add(b).ok_or(OracleError::CrossRateOverflow)
+}
+
+fn mul(a: Decimal, b: Decimal) -> Result<Decimal, OracleError> {
+    a.checked_mul(b).ok_or(OracleError::CrossRateOverflow)
+}
+
+fn div(a: Decimal, b: Decimal) -> Result<Decimal, OracleError> {
+    a.checked_div(b).ok_or(OracleError::CrossRateOverflow)
+}
+
+#[cfg(test)]
+mod cross_rate_tests {
+    use scrypto::prelude::*;
+
+    use crate::cross_rate::{divide, invert, multiply, DERIVED_NONCE};
+    use crate::price_message::PriceMessage;
+
+    fn price_message(market_id: &str, price: Decimal, created_at: u64) -> PriceMessage {
+        PriceMessage {
+            market_id: market_id.to_string(),
+            price,
+            nonce: 1,
+            created_at,
+            bid: price - dec!(0.5),
+            ask: price + dec!(0.5),
+            confidence: dec!(0.1),
+            valid_until: created_at + 60,
+        }
+    }
+
+    #[test]
+    pub fn test_multiply() {
+        let xrd_usdt = price_message("GATEIO:XRD_USDT", dec!(2), 1000);
+        let usdt_eur = price_message("ECB:USDT_EUR", dec!(4), 1030);
+
+        let xrd_eur = multiply(&xrd_usdt, &usdt_eur).unwrap();
+
+        assert_eq!(xrd_eur.market_id, "GATEIO:XRD_USDT*ECB:USDT_EUR");
+        assert_eq!(xrd_eur.price, dec!(8));
+        assert_eq!(xrd_eur.bid, dec!(5.25));
+        assert_eq!(xrd_eur.ask, dec!(11.25));
+        assert_eq!(xrd_eur.confidence, dec!(0.6));
+        assert_eq!(xrd_eur.created_at, 1000);
+        assert_eq!(xrd_eur.valid_until, 1060);
+        assert_eq!(xrd_eur.nonce, DERIVED_NONCE);
+    }
+
+    #[test]
+    pub fn test_divide() {
+        let xrd_usd = price_message("GATEIO:XRD_USD", dec!(8), 1030);
+        let eur_usd = price_message("ECB:EUR_USD", dec!(2), 1000);
+
+        let xrd_eur = divide(&xrd_usd, &eur_usd).unwrap();
+
+        assert_eq!(xrd_eur.market_id, "GATEIO:XRD_USD/ECB:EUR_USD");
+        assert_eq!(xrd_eur.price, dec!(4));
+        assert_eq!(xrd_eur.bid, dec!(3));
+        assert_eq!(xrd_eur.ask, dec!(5.666666666666666666));
+        assert_eq!(xrd_eur.confidence, dec!(0.25));
+        assert_eq!(xrd_eur.created_at, 1000);
+    }
+
+    #[test]
+    pub fn test_invert() {
+        let xrd_usdt = price_message("GATEIO:XRD_USDT", dec!(2), 1000);
+
+        let usdt_xrd = invert(&xrd_usdt).unwrap();
+
+        assert_eq!(usdt_xrd.market_id, "1/GATEIO:XRD_USDT");
+        assert_eq!(usdt_xrd.price, dec!(0.5));
+        assert_eq!(usdt_xrd.bid, dec!(0.4));
+        assert_eq!(usdt_xrd.ask, dec!(0.666666666666666666));
+        assert_eq!(usdt_xrd.confidence, dec!(0.025));
+    }
+
+    #[test]
+    pub fn test_non_positive_price_fails() {
+        let zero_bid = price_message("TEST:MARKET", dec!(0.5), 1000);
+
+        assert!(invert(&zero_bid).is_err());
+        assert!(divide(&price_message("TEST:OTHER", dec!(1), 1000), &zero_bid).is_err());
+    }
+
+    #[test]
+    pub fn test_overflow_fails() {
+        let huge = price_message("TEST:HUGE", Decimal::MAX - dec!(1), 1000);
+
+        assert!(multiply(&huge, &huge).is_err());
+        assert!(divide(&huge, &price_message("TEST:SMALL", dec!(0.6), 1000)).is_err());
+    }
+}
@@ -7,25 +7,36 @@ use crate::utils::get_time;
 #[derive(ScryptoSbor)]
 pub struct Status {
     pub amount: Decimal,
+    pub requested_price: Option<Decimal>,
 }
 
 #[blueprint]
 mod gumball_machine {
     enable_method_auth! {
+        roles {
+            oracle => updatable_by: [];
+        },
         // decide which methods are public and which are restricted to the component's owner
         methods {
             buy_gumball => PUBLIC;
             get_status => PUBLIC;
             withdraw_earnings => restrict_to: [OWNER];
             refill_gumball_machine => restrict_to: [OWNER];
+            request_price => restrict_to: [OWNER];
+            receive_price => restrict_to: [oracle];
         }
     }
     struct GumballMachine {
-        gum_resource_manager: ResourceManager,
+        gum_resource_manager: FungibleResourceManager,
         gumballs: Vault,
         collected_xrd: Vault,
         price_lifetime: u64,
         oracle_address: ComponentAddress,
+        oracle_subscription: NonFungibleVault,
+        // Last price delivered by the oracle for a request of this component
+        requested_price: Option<Decimal>,
+        // Request of this component waiting for its price, with the requested market
+        pending_request: Option<(u64, String)>,
     }
 
     impl GumballMachine {
@@ -33,6 +44,7 @@ mod gumball_machine {
         pub fn instantiate_gumball_machine(
             price_lifetime: u64,
             oracle_address: ComponentAddress,
+            oracle_subscription: NonFungibleBucket,
             dapp_definition: ComponentAddress,
         ) -> (Global<GumballMachine>, Bucket) {
             // reserve an address for the component
@@ -71,23 +83,30 @@ mod gumball_machine {
 
             // populate a GumballMachine struct and instantiate a new component
             let component = Self {
-                gum_resource_manager: bucket_of_gumballs.resource_manager(),
+                gum_resource_manager: bucket_of_gumballs.resource_address().into(),
                 gumballs: Vault::with_bucket(bucket_of_gumballs),
                 collected_xrd: Vault::new(XRD),
                 price_lifetime,
                 oracle_address,
+                oracle_subscription: NonFungibleVault::with_bucket(oracle_subscription),
+                requested_price: None,
+                pending_request: None,
             }
             .instantiate()
             // Assign the component owner role to the possessor of the owner_badge resource
             .prepare_to_globalize(OwnerRole::Fixed(rule!(require(
                 owner_badge.resource_address()
             ))))
+            // Requested prices can only be delivered by the oracle itself
+            .roles(roles! {
+                oracle => rule!(require(global_caller(oracle_address)));
+            })
             .with_address(address_reservation)
             .metadata(metadata!(roles {
-            metadata_setter => rule!(deny_all);
-            metadata_setter_updater => rule!(deny_all);
-            metadata_locker => rule!(deny_all);
-            metadata_locker_updater => rule!(deny_all);
+            metadata_setter => OWNER;
+            metadata_setter_updater => OWNER;
+            metadata_locker => OWNER;
+            metadata_locker_updater => OWNER;
             },
             init {
                     "dapp_definition" => GlobalAddress::from(dapp_definition), updatable;
@@ -127,6 +146,7 @@ mod gumball_machine {
         pub fn get_status(&self) -> Status {
             Status {
                 amount: self.gumballs.amount(),
+                requested_price: self.requested_price,
             }
         }
 
@@ -141,16 +161,48 @@ mod gumball_machine {
             // requires the owner badge
             let gumball_amount = 100 - self.gumballs.amount();
             self.gumballs
-                .put(self.gum_resource_manager.mint(gumball_amount));
+                .put(self.gum_resource_manager.mint(gumball_amount).into());
         }
 
-        fn make_all_trading_checks(&mut self, message: String, signature: String) -> PriceMessage {
+        pub fn request_price(&mut self, market_id: String) -> u64 {
             let morpher_oracle: Global<MorpherOracle> = self.oracle_address.into();
 
-            let price_message = morpher_oracle.check_price_input(
-                // self.subscription.create_proof_of_non_fungibles(&ids),
-                message, signature,
+            let request_id = morpher_oracle.request_price(
+                market_id.clone(),
+                Runtime::global_address(),
+                "receive_price".to_string(),
+                self.subscription_proof(),
             );
+            self.pending_request = Some((request_id, market_id));
+
+            request_id
+        }
+
+        // called back by the oracle once it fulfilled a request of this component
+        pub fn receive_price(&mut self, request_id: u64, price_message: PriceMessage) {
+            // anyone can request a price with this component as callback, so only the pending
+            // request of this component is accepted
+            assert!(
+                self.pending_request == Some((request_id, price_message.market_id.clone())),
+                "This price was not requested by this component"
+            );
+
+            self.pending_request = None;
+            self.requested_price = Some(price_message.price);
+        }
+
+        // the oracle calls are paid by the subscription held by this component
+        fn subscription_proof(&self) -> NonFungibleProof {
+            let subscription_id = self.oracle_subscription.non_fungible_local_id();
+            self.oracle_subscription
+                .create_proof_of_non_fungibles(&indexset!(subscription_id))
+        }
+
+        fn make_all_trading_checks(&mut self, message: String, signature: String) -> PriceMessage {
+            let morpher_oracle: Global<MorpherOracle> = self.oracle_address.into();
+
+            let price_message =
+                morpher_oracle.check_price_input(message, signature, self.subscription_proof());
 
             // Check that the price lifetime has not been surpassed
             assert!(
@@ -1,5 +1,11 @@
 pub mod oracle;
+pub mod oracle_error;
 pub mod price_message;
+pub mod basket_price_message;
 pub mod oracle_request_message;
+pub mod migration_receipt;
 pub mod gumball;
+pub mod twap;
+pub mod rate_limit;
+pub mod cross_rate;
 pub mod utils;
@@ -0,0 +1,244 @@
+use std::str::FromStr;
+
+use scrypto::prelude::*;
+
+use crate::oracle::BillingMode;
+use crate::oracle_error::OracleError;
+
+/// Prefix of signed migration receipts, so that a signature over a receipt cannot be passed off as
+/// the signature of another message.
+pub const MIGRATION_RECEIPT_PREFIX: &str = "migration-receipt-v1:";
+
+/// Subscription exported by an oracle deployment, to be imported in another one once signed by
+/// the oracle.
+#[derive(ScryptoSbor, Clone)]
+pub struct MigrationReceipt {
+    // Address of the oracle which exported the subscription
+    pub source: String,
+    // Address of the oracle allowed to import the subscription
+    pub target: String,
+    // Address of the account receiving the imported subscription
+    pub recipient: String,
+    pub subscription_id: String,
+    pub remaining_time: u64,
+    pub remaining_calls: u64,
+    pub authorized_pub_key: String,
+    pub exported_at: u64,
+    pub trial: bool,
+    pub complimentary: bool,
+    pub billing_mode: BillingMode,
+    // Maximum number of calls and window of the rate limit, if any
+    pub rate_limit: Option<(u64, u64)>,
+}
+
+impl MigrationReceipt {
+    pub fn to_string(&self) -> String {
+        let billing_mode = match self.billing_mode {
+            BillingMode::Monthly => "Monthly",
+            BillingMode::PayPerCall => "PayPerCall",
+        };
+        let rate_limit = self
+            .rate_limit
+            .map(|(max_calls, window)| format!("{}/{}", max_calls, window))
+            .unwrap_or_default();
+
+        // The subscription id comes last, integer ids are wrapped in '#'
+        return format!(
+            "{}{}##{}##{}##{}##{}##{}##{}##{}##{}##{}##{}##{}",
+            MIGRATION_RECEIPT_PREFIX,
+            self.source,
+            self.target,
+            self.recipient,
+            self.remaining_time,
+            self.remaining_calls,
+            self.authorized_pub_key,
+            self.exported_at,
+            self.trial,
+            self.complimentary,
+            rate_limit,
+            billing_mode,
+            self.subscription_id
+        );
+    }
+}
+
+impl FromStr for MigrationReceipt {
+    type Err = OracleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s
+            .strip_prefix(MIGRATION_RECEIPT_PREFIX)
+            .ok_or(OracleError::InvalidMessageFormat)?;
+        let parts: Vec<&str> = s.splitn(12, "##").collect();
+
+        if parts.len() != 12 {
+            Err(OracleError::InvalidMessageFormat)
+        } else {
+            let source = parts.first().unwrap().to_string();
+            let target = parts.get(1).unwrap().to_string();
+            let recipient = parts.get(2).unwrap().to_string();
+            if source.is_empty() || target.is_empty() || recipient.is_empty() {
+                return Err(OracleError::InvalidAddress);
+            }
+
+            let remaining_time = parts
+                .get(3)
+                .unwrap()
+                .parse()
+                .map_err(|_| OracleError::InvalidRemainingTime)?;
+
+            let remaining_calls = parts
+                .get(4)
+                .unwrap()
+                .parse()
+                .map_err(|_| OracleError::InvalidRemainingCalls)?;
+
+            let authorized_pub_key = parts.get(5).unwrap().to_string();
+
+            let exported_at = parts
+                .get(6)
+                .unwrap()
+                .parse()
+                .map_err(|_| OracleError::InvalidCreationDate)?;
+
+            let trial = parts
+                .get(7)
+                .unwrap()
+                .parse()
+                .map_err(|_| OracleError::InvalidMessageFormat)?;
+
+            let complimentary = parts
+                .get(8)
+                .unwrap()
+                .parse()
+                .map_err(|_| OracleError::InvalidMessageFormat)?;
+
+            let rate_limit = match *parts.get(9).unwrap() {
+                "" => None,
+                rate_limit => {
+                    let (max_calls, window) = rate_limit
+                        .split_once('/')
+                        .ok_or(OracleError::InvalidRateLimit)?;
+                    Some((
+                        max_calls
+                            .parse()
+                            .map_err(|_| OracleError::InvalidRateLimit)?,
+                        window.parse().map_err(|_| OracleError::InvalidRateLimit)?,
+                    ))
+                }
+            };
+
+            let billing_mode = match *parts.get(10).unwrap() {
+                "Monthly" => BillingMode::Monthly,
+                "PayPerCall" => BillingMode::PayPerCall,
+                _ => return Err(OracleError::InvalidMessageFormat),
+            };
+
+            let subscription_id = parts.get(11).unwrap().to_string();
+            if subscription_id.is_empty() {
+                return Err(OracleError::InvalidSubscriptionId);
+            }
+
+            Ok(MigrationReceipt {
+                source,
+                target,
+                recipient,
+                subscription_id,
+                remaining_time,
+                remaining_calls,
+                authorized_pub_key,
+                exported_at,
+                trial,
+                complimentary,
+                billing_mode,
+                rate_limit,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod migration_receipt_tests {
+    use scrypto::prelude::*;
+
+    use crate::migration_receipt::MigrationReceipt;
+    use crate::oracle::BillingMode;
+
+    #[test]
+    pub fn test_to_string() {
+        let migration_receipt = MigrationReceipt {
+            source: "component_sim1TEST".to_string(),
+            target: "component_sim1NEW".to_string(),
+            recipient: "account_sim1TEST".to_string(),
+            subscription_id: "#1#".to_string(),
+            remaining_time: 3600,
+            remaining_calls: 1000,
+            authorized_pub_key: "abcTEST".to_string(),
+            exported_at: 1230,
+            trial: false,
+            complimentary: true,
+            billing_mode: BillingMode::Monthly,
+            rate_limit: Some((10, 60)),
+        };
+
+        assert_eq!(
+            migration_receipt.to_string(),
+            "migration-receipt-v1:component_sim1TEST##component_sim1NEW##account_sim1TEST##\
+            3600##1000##abcTEST##1230##false##true##10/60##Monthly###1#"
+        );
+
+        let parsed = MigrationReceipt::from_str(&migration_receipt.to_string()).unwrap();
+        assert_eq!(parsed.subscription_id, "#1#");
+        assert_eq!(parsed.rate_limit, Some((10, 60)));
+    }
+
+    #[test]
+    pub fn from_string_test() {
+        let migration_receipt = MigrationReceipt::from_str(
+            "migration-receipt-v1:component_sim1TEST##component_sim1NEW##account_sim1TEST##\
+            3600##1000####1230##true##false####PayPerCall##{a-b-c-d}",
+        )
+        .unwrap();
+        assert!(
+            migration_receipt.source == "component_sim1TEST"
+                && migration_receipt.target == "component_sim1NEW"
+                && migration_receipt.recipient == "account_sim1TEST"
+                && migration_receipt.subscription_id == "{a-b-c-d}"
+                && migration_receipt.remaining_time == 3600
+                && migration_receipt.remaining_calls == 1000
+                && migration_receipt.authorized_pub_key.is_empty()
+                && migration_receipt.exported_at == 1230
+                && migration_receipt.trial
+                && !migration_receipt.complimentary
+                && migration_receipt.billing_mode == BillingMode::PayPerCall
+                && migration_receipt.rate_limit.is_none()
+        );
+
+        // Receipts without the prefix are not accepted
+        assert!(MigrationReceipt::from_str(
+            "component_sim1TEST##component_sim1NEW##account_sim1TEST##3600##1000####1230##false##false####Monthly###1#"
+        )
+        .is_err());
+        assert!(MigrationReceipt::from_str(
+            "migration-receipt-v1:component_sim1TEST##component_sim1NEW##account_sim1TEST##3600##1000"
+        )
+        .is_err());
+        assert!(MigrationReceipt::from_str(
+            "migration-receipt-v1:##component_sim1NEW##account_sim1TEST##3600##1000####1230##false##false####Monthly###1#"
+        )
+        .is_err());
+        // Receipts are bound to a recipient
+        assert!(MigrationReceipt::from_str(
+            "migration-receipt-v1:component_sim1TEST##component_sim1NEW####3600##1000####1230##false##false####Monthly###1#"
+        )
+        .is_err());
+        assert!(MigrationReceipt::from_str(
+            "migration-receipt-v1:component_sim1TEST##component_sim1NEW##account_sim1TEST##-1##1000####1230##false##false####Monthly###1#"
+        )
+        .is_err());
+        assert!(MigrationReceipt::from_str(
+            "migration-receipt-v1:component_sim1TEST##component_sim1NEW##account_sim1TEST##3600##1000####1230##false##false##10##Monthly###1#"
+        )
+        .is_err());
+    }
+}
@@ -2,54 +2,408 @@ use std::cmp::max;
 
 use scrypto::prelude::*;
 
+use crate::basket_price_message::BasketPriceMessage;
+use crate::cross_rate::DERIVED_NONCE;
+use crate::migration_receipt::MigrationReceipt;
+use crate::oracle_error::OracleError;
 use crate::oracle_request_message::OracleRequestMessage;
 use crate::price_message::PriceMessage;
+use crate::rate_limit::{RateLimit, MIN_RATE_LIMIT_WINDOW};
+use crate::twap::TwapAccumulator;
 use crate::utils::*;
 
+#[derive(ScryptoSbor, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BillingMode {
+    // Paid by the month, with API_CALLS_PER_MONTH calls per month
+    Monthly,
+    // Every call is paid out of the subscription credit, the subscription never expires
+    PayPerCall,
+}
+
 #[derive(NonFungibleData, ScryptoSbor)]
 pub struct OracleSubscription {
+    // Display fields shown by wallets
+    pub name: String,
+    pub key_image_url: Url,
+    #[mutable]
+    pub tier: String,
+    // Human readable expiration_time
+    #[mutable]
+    pub expiry_date: String,
     #[mutable]
     pub expiration_time: u64,
+    // Calls made so far, counted by the oracle backend and synced by the admin
     #[mutable]
     pub cur_nonce: u64,
     #[mutable]
     pub max_nonce: u64,
     #[mutable]
     pub authorized_pub_key: String,
+    // Calls made after expiration, during the grace period
+    #[mutable]
+    pub grace_calls: u64,
+    // Cleared once the trial is renewed as a paid subscription
+    #[mutable]
+    pub trial: bool,
+    // Granted for free by the admin, cleared once renewed as a paid subscription
+    #[mutable]
+    pub complimentary: bool,
+    pub billing_mode: BillingMode,
+    // Suspended subscriptions cannot make calls until reinstated by the admin
+    #[mutable]
+    pub suspended: bool,
+    #[mutable]
+    pub suspension_reason: String,
+    // Optional limit on the call rate, set by the subscriber
+    #[mutable]
+    pub rate_limit: Option<RateLimit>,
+}
+
+/// Badge making calls on behalf of a subscription, out of a quota carved from the subscription.
+#[derive(NonFungibleData, ScryptoSbor)]
+pub struct SubscriptionDelegate {
+    pub parent_id: NonFungibleLocalId,
+    pub max_calls: u64,
+    pub expiration_time: u64,
+    #[mutable]
+    pub calls_used: u64,
+    #[mutable]
+    pub revoked: bool,
+}
+
+#[derive(NonFungibleData, ScryptoSbor)]
+pub struct SignerStake {
+    pub public_key: OraclePublicKey,
+    // Staked XRD, set to 0 once slashed
+    #[mutable]
+    pub amount: Decimal,
+    // Time from which the stake can be withdrawn, None while the key is in the signer set
+    #[mutable]
+    pub unbonding_until: Option<u64>,
+}
+
+#[derive(ScryptoSbor, Clone)]
+pub struct EquivocationReport {
+    pub price_a: Decimal,
+    pub price_b: Decimal,
+    pub reported_at: u64,
+}
+
+#[derive(ScryptoSbor)]
+pub struct OracleConfig {
+    pub signers: Vec<OraclePublicKey>,
+    pub emergency_pub_key: Option<OraclePublicKey>,
+    pub monthly_subscription_fee: Decimal,
+    pub subscription_resource: ResourceAddress,
+    pub subscription_image_url: String,
+    pub delegate_resource: ResourceAddress,
+    pub max_price_deviation: Option<Decimal>,
+    pub price_deviation_window: u64,
+    pub trial_days: u64,
+    pub trial_calls: u64,
+    pub grace_period: u64,
+    pub grace_period_calls: u64,
+    pub price_per_call: Option<Decimal>,
+    pub low_credit_threshold: Decimal,
+    pub stake_resource: ResourceAddress,
+    pub min_signer_stake: Option<Decimal>,
+    pub unbonding_delay: u64,
+    pub disable_equivocating_signers: bool,
+}
+
+#[derive(ScryptoSbor)]
+pub struct SubscriptionInfo {
+    pub subscription: OracleSubscription,
+    /// Seconds until expiration, 0 once expired.
+    pub remaining_time: u64,
+    /// Calls that can still be made, including the grace period once expired.
+    pub remaining_calls: u64,
+    pub credit: Decimal,
+}
+
+#[derive(ScryptoSbor, Clone)]
+pub struct LastPrice {
+    pub price: Decimal,
+    pub created_at: u64,
+}
+
+#[derive(ScryptoSbor, Clone)]
+pub struct PriceRequest {
+    pub market_id: String,
+    pub callback_component: ComponentAddress,
+    pub callback_method: String,
+    pub requested_at: u64,
 }
 
 #[derive(ScryptoEvent, ScryptoSbor)]
 pub struct OracleSubscriptionUpdate {
     pub new_expiration_time: u64,
     pub nft_id: NonFungibleLocalId,
+    // Granted for free by the admin, to be excluded from revenue accounting
+    pub complimentary: bool,
+}
+
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct OracleSubscriptionGracePeriodCall {
+    pub nft_id: NonFungibleLocalId,
+    pub expiration_time: u64,
+    pub grace_calls_left: u64,
+}
+
+/// Emitted by the call which used up the rate limit, the following calls fail until the window rolls.
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct OracleSubscriptionThrottled {
+    pub nft_id: NonFungibleLocalId,
+    pub max_calls: u64,
+    pub window: u64,
+}
+
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct SubscriptionDelegateMinted {
+    pub parent_id: NonFungibleLocalId,
+    pub delegate_id: NonFungibleLocalId,
+    pub max_calls: u64,
+    pub expiration_time: u64,
+}
+
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct SubscriptionDelegateRevoked {
+    pub parent_id: NonFungibleLocalId,
+    pub delegate_id: NonFungibleLocalId,
+    pub returned_calls: u64,
+}
+
+/// Emitted when a subscription is burnt to be moved to another oracle deployment.
+///
+/// The oracle signs `receipt` off-ledger, the subscriber then imports it with `import_subscription`.
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct OracleSubscriptionExported {
+    pub nft_id: NonFungibleLocalId,
+    pub receipt: String,
+}
+
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct OracleSubscriptionImported {
+    pub nft_id: NonFungibleLocalId,
+    pub source: String,
+    pub source_subscription_id: String,
+}
+
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct OracleSubscriptionSuspended {
+    pub nft_id: NonFungibleLocalId,
+    pub reason: String,
+}
+
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct OracleSubscriptionReinstated {
+    pub nft_id: NonFungibleLocalId,
+}
+
+/// Emitted by the call which brought the credit of a subscription below the low credit threshold.
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct OracleSubscriptionLowCredit {
+    pub nft_id: NonFungibleLocalId,
+    pub credit: Decimal,
+}
+
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct PriceRequested {
+    pub request_id: u64,
+    pub market_id: String,
+    pub callback_component: ComponentAddress,
+    pub callback_method: String,
+}
+
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct PriceRequestFulfilled {
+    pub request_id: u64,
+    pub market_id: String,
+    pub price: Decimal,
+}
+
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct SignerStaked {
+    pub public_key: OraclePublicKey,
+    pub stake_id: NonFungibleLocalId,
+    pub amount: Decimal,
+}
+
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct StakedSignerApproved {
+    pub public_key: OraclePublicKey,
+    pub stake_id: NonFungibleLocalId,
+}
+
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct SignerUnbonding {
+    pub public_key: OraclePublicKey,
+    pub stake_id: NonFungibleLocalId,
+    pub unbonding_until: u64,
+}
+
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct SignerSlashed {
+    pub public_key: OraclePublicKey,
+    pub stake_id: NonFungibleLocalId,
+    pub amount: Decimal,
+    pub market_id: String,
+    pub nonce: u64,
+}
+
+#[derive(ScryptoEvent, ScryptoSbor)]
+pub struct EquivocationReported {
+    pub public_key: OraclePublicKey,
+    pub market_id: String,
+    pub nonce: u64,
+    pub price_a: Decimal,
+    pub price_b: Decimal,
+    pub signer_disabled: bool,
 }
 
 #[blueprint]
-#[events(OracleSubscriptionUpdate)]
+#[events(
+    OracleSubscriptionUpdate,
+    OracleSubscriptionGracePeriodCall,
+    OracleSubscriptionThrottled,
+    OracleSubscriptionLowCredit,
+    OracleSubscriptionExported,
+    OracleSubscriptionImported,
+    OracleSubscriptionSuspended,
+    OracleSubscriptionReinstated,
+    SubscriptionDelegateMinted,
+    SubscriptionDelegateRevoked,
+    PriceRequested,
+    PriceRequestFulfilled,
+    SignerStaked,
+    StakedSignerApproved,
+    SignerUnbonding,
+    SignerSlashed,
+    EquivocationReported
+)]
 mod morpher_oracle {
 
     enable_method_auth! {
         roles{
             admin => updatable_by: [SELF];
+            keeper => updatable_by: [admin];
         }, methods {
             update_subscription_pub_key => PUBLIC;
+            set_subscription_rate_limit => PUBLIC;
             new_subscription => PUBLIC;
+            new_trial_subscription => PUBLIC;
+            new_metered_subscription => PUBLIC;
+            mint_subscription_delegate => PUBLIC;
+            revoke_subscription_delegate => PUBLIC;
+            get_subscription_delegate => PUBLIC;
             renew_subscription => PUBLIC;
+            export_subscription => PUBLIC;
+            import_subscription => PUBLIC;
+            top_up_subscription_credit => PUBLIC;
+            withdraw_subscription_credit => PUBLIC;
+            get_subscription_credit => PUBLIC;
+            process_renewals => PUBLIC;
             check_price_input => PUBLIC;
+            check_price_input_with_royalty => PUBLIC;
+            check_emergency_price_input => PUBLIC;
+            check_price_input_with_max_confidence => PUBLIC;
+            verify_price_input => PUBLIC;
+            check_basket_price_input => PUBLIC;
+            get_twap => PUBLIC;
+            get_config => PUBLIC;
+            get_subscription => PUBLIC;
+            is_nonce_used => PUBLIC;
+            remaining_calls => PUBLIC;
+            remaining_time => PUBLIC;
+            request_price => PUBLIC;
+            stake_signer => PUBLIC;
+            unstake_signer => PUBLIC;
+            withdraw_signer_stake => PUBLIC;
+            report_equivocation => PUBLIC;
+            get_equivocation_report => PUBLIC;
+            fulfil => restrict_to: [keeper];
             collect_subscription_fees => restrict_to: [admin];
             set_subscription_cur_nonce => restrict_to: [admin];
+            grant_complimentary_subscription => restrict_to: [admin];
+            suspend_subscription => restrict_to: [admin];
+            reinstate_subscription => restrict_to: [admin];
+            set_price_deviation_guard => restrict_to: [admin];
+            set_emergency_pub_key => restrict_to: [admin];
+            set_migration_pub_key => restrict_to: [admin];
+            add_signer => restrict_to: [admin];
+            remove_signer => restrict_to: [admin];
+            set_trial_config => restrict_to: [admin];
+            set_grace_period => restrict_to: [admin];
+            set_subscription_image_url => restrict_to: [admin];
+            set_metered_billing => restrict_to: [admin];
+            set_signer_staking => restrict_to: [admin];
+            approve_staked_signer => restrict_to: [admin];
+            slash_signer => restrict_to: [admin];
+            slash_signer_for_wrong_price => restrict_to: [admin];
+            set_disable_equivocating_signers => restrict_to: [admin];
         }
     }
 
+    const SECONDS_IN_A_DAY: u64 = 86_400;
     const SECONDS_IN_A_MONTH: u64 = 2_592_000;
     const API_CALLS_PER_MONTH: u64 = 1_000_000;
 
     pub struct MorpherOracle {
-        authorized_pub_key: Bls12381G1PublicKey,
+        // Keys accepted for oracle signatures, a message needs to be signed by one of them
+        signers: Vec<OraclePublicKey>,
         monthly_subscription_fee: Decimal,
         subscription_fees_vault: FungibleVault,
-        subscription_manager: ResourceManager,
+        subscription_manager: NonFungibleResourceManager,
+        // Integer id of the next minted subscription
+        next_subscription_id: u64,
+        // Image of newly minted subscriptions
+        subscription_image_url: String,
+        delegate_manager: NonFungibleResourceManager,
         used_nonce: HashSet<u64>,
+        // Maximum deviation in percent from the last accepted price of a market, None disables the guard
+        max_price_deviation: Option<Decimal>,
+        // The deviation is limited for this long after the last accepted price, then it is reset
+        price_deviation_window: u64,
+        emergency_pub_key: Option<OraclePublicKey>,
+        last_prices: KeyValueStore<String, LastPrice>,
+        twap_accumulators: KeyValueStore<String, TwapAccumulator>,
+        price_requests: KeyValueStore<u64, PriceRequest>,
+        next_price_request_id: u64,
+        // Trial subscriptions are disabled while trial_days is 0
+        trial_days: u64,
+        trial_calls: u64,
+        // Account owner badges which already claimed a trial, mapped to their trial subscription
+        claimed_trials: KeyValueStore<NonFungibleLocalId, NonFungibleLocalId>,
+        grace_period: u64,
+        grace_period_calls: u64,
+        // Prepaid XRD of subscriptions, used to renew them for a month once they expire
+        subscription_credits: KeyValueStore<NonFungibleLocalId, FungibleVault>,
+        // Fee of a call of a pay per call subscription, these subscriptions are unavailable while None
+        price_per_call: Option<Decimal>,
+        low_credit_threshold: Decimal,
+        // Consumed migration receipts, by their source oracle and subscription id which identify a
+        // receipt since exporting burns the subscription
+        imported_subscriptions: KeyValueStore<(String, String), NonFungibleLocalId>,
+        // Key signing the migration receipts accepted by import_subscription, imports are closed
+        // while None
+        migration_pub_key: Option<OraclePublicKey>,
+        // Seconds after its export during which a receipt can be imported
+        max_migration_receipt_age: u64,
+        // Number of unrevoked delegates of a subscription
+        active_delegates: KeyValueStore<NonFungibleLocalId, u64>,
+        stake_manager: NonFungibleResourceManager,
+        stake_vault: FungibleVault,
+        // Staked keys mapped to their stake, kept while unbonding so that the stake can still be slashed
+        staked_signers: HashMap<OraclePublicKey, NonFungibleLocalId>,
+        // Signer staking is closed while min_signer_stake is None
+        min_signer_stake: Option<Decimal>,
+        unbonding_delay: u64,
+        // Reported conflicting prices, by signer, market and nonce
+        equivocation_reports: KeyValueStore<(OraclePublicKey, String, u64), EquivocationReport>,
+        // Number of reported conflicting prices per signer, these keys cannot stake anymore
+        equivocating_signers: KeyValueStore<OraclePublicKey, u64>,
+        // Whether a reported signer is removed from the signer set right away
+        disable_equivocating_signers: bool,
         // transient_resource_manager: ResourceManager, //coming in V2
     }
 
@@ -57,8 +411,17 @@ mod morpher_oracle {
         pub fn instantiate(
             authorized_public_key: String,
             monthly_subscription_fee: Decimal,
+            check_price_royalty: RoyaltyAmount,
             dapp_definition: ComponentAddress,
+            subscription_image_url: String,
         ) -> (Global<MorpherOracle>, FungibleBucket) {
+            // Wallets show a blank subscription NFT without an image
+            assert!(
+                !subscription_image_url.is_empty(),
+                "{}",
+                OracleError::MissingSubscriptionImageUrl
+            );
+
             // Creates a reservation for this component so that we can set up the correct roles
             let (address_reservation, component_address) =
                 Runtime::allocate_component_address(<MorpherOracle>::blueprint_id());
@@ -93,7 +456,8 @@ mod morpher_oracle {
             // Creates the subscription NFT that can be minted, updated and burnt only by this blueprint.
             // It can only be traded by this component.
             // Its metadata can be changed with the admin badge.
-            let subscription_manager = ResourceBuilder::new_ruid_non_fungible::<OracleSubscription>(OwnerRole::None)
+            // Subscriptions are numbered sequentially: #1#, #2#, ...
+            let subscription_manager = ResourceBuilder::new_integer_non_fungible::<OracleSubscription>(OwnerRole::None)
                 .burn_roles(
                     burn_roles!(burner => rule!(require(global_caller(component_address))); burner_updater => rule!(deny_all);)
                 )
@@ -116,6 +480,47 @@ mod morpher_oracle {
                         "dapp_definition" => GlobalAddress::from(dapp_definition), updatable;
                     }))
                 .create_with_no_initial_supply();
+
+            // Creates the delegate NFT that lets a component call on behalf of a subscription.
+            // Only this blueprint can mint and update it, delegates are revoked instead of burnt.
+            let delegate_manager = ResourceBuilder::new_ruid_non_fungible::<SubscriptionDelegate>(OwnerRole::None)
+                .mint_roles(mint_roles! { minter => rule!(require(global_caller(component_address))); minter_updater => rule!(deny_all);})
+                .non_fungible_data_update_roles(non_fungible_data_update_roles!( non_fungible_data_updater => rule!(require(global_caller(component_address)));
+                    non_fungible_data_updater_updater => rule!(deny_all);))
+                .metadata(metadata!(roles {
+                    metadata_setter => rule!(require(admin_badge.resource_address()));
+                    metadata_setter_updater => rule!(require(admin_badge.resource_address()));
+                    metadata_locker => rule!(require(admin_badge.resource_address()));
+                    metadata_locker_updater => rule!(require(admin_badge.resource_address()));
+                },
+                init {
+                        "name" => "Morpher subscription delegate".to_string(), updatable;
+                        "description" => "Makes calls on behalf of a Morpher subscription.", updatable;
+                        "dapp_definition" => GlobalAddress::from(dapp_definition), updatable;
+                    }))
+                .create_with_no_initial_supply();
+
+            // Creates the stake NFT handed to signers which joined by staking XRD.
+            // Only this blueprint can mint, update and burn it.
+            let stake_manager = ResourceBuilder::new_ruid_non_fungible::<SignerStake>(OwnerRole::None)
+                .burn_roles(
+                    burn_roles!(burner => rule!(require(global_caller(component_address))); burner_updater => rule!(deny_all);)
+                )
+                .mint_roles(mint_roles! { minter => rule!(require(global_caller(component_address))); minter_updater => rule!(deny_all);})
+                .non_fungible_data_update_roles(non_fungible_data_update_roles!( non_fungible_data_updater => rule!(require(global_caller(component_address)));
+                    non_fungible_data_updater_updater => rule!(deny_all);))
+                .metadata(metadata!(roles {
+                    metadata_setter => rule!(require(admin_badge.resource_address()));
+                    metadata_setter_updater => rule!(require(admin_badge.resource_address()));
+                    metadata_locker => rule!(require(admin_badge.resource_address()));
+                    metadata_locker_updater => rule!(require(admin_badge.resource_address()));
+                },
+                init {
+                        "name" => "Morpher signer stake".to_string(), updatable;
+                        "description" => "XRD staked by a Morpher oracle signer.", updatable;
+                        "dapp_definition" => GlobalAddress::from(dapp_definition), updatable;
+                    }))
+                .create_with_no_initial_supply();
                 
             // // Define a "transient" resource which can never be deposited once created, only burned
             // let transient_price_message_manager = ResourceBuilder::new_ruid_non_fungible::<PriceMessage>(OwnerRole::None)
@@ -141,24 +546,127 @@ mod morpher_oracle {
             //     .create_with_no_initial_supply();
 
             let component = Self {
-                authorized_pub_key: Bls12381G1PublicKey::from_str(authorized_public_key.as_str())
-                    .expect("The given public key is not valid"),
+                signers: vec![OraclePublicKey::parse(
+                    OracleKeyType::Bls12381,
+                    authorized_public_key.as_str(),
+                )],
                 monthly_subscription_fee,
                 subscription_fees_vault: FungibleVault::new(XRD),
                 subscription_manager,
+                next_subscription_id: 1,
+                subscription_image_url,
+                delegate_manager,
                 used_nonce: HashSet::new(),
+                max_price_deviation: None,
+                price_deviation_window: 0,
+                emergency_pub_key: None,
+                last_prices: KeyValueStore::new(),
+                twap_accumulators: KeyValueStore::new(),
+                price_requests: KeyValueStore::new(),
+                next_price_request_id: 0,
+                trial_days: 0,
+                trial_calls: 0,
+                claimed_trials: KeyValueStore::new(),
+                grace_period: 0,
+                grace_period_calls: 0,
+                subscription_credits: KeyValueStore::new(),
+                price_per_call: None,
+                low_credit_threshold: Decimal::ZERO,
+                imported_subscriptions: KeyValueStore::new(),
+                migration_pub_key: None,
+                max_migration_receipt_age: 0,
+                active_delegates: KeyValueStore::new(),
+                stake_manager,
+                stake_vault: FungibleVault::new(XRD),
+                staked_signers: HashMap::new(),
+                min_signer_stake: None,
+                unbonding_delay: 0,
+                equivocation_reports: KeyValueStore::new(),
+                equivocating_signers: KeyValueStore::new(),
+                disable_equivocating_signers: false,
             }
             .instantiate()
-            .prepare_to_globalize(OwnerRole::None)
+            // The admin badge owns the component, which lets it set and lock the metadata
+            .prepare_to_globalize(OwnerRole::Fixed(rule!(require(
+                admin_badge.resource_address()
+            ))))
             .roles(roles! {
                 admin => rule!(require(admin_badge.resource_address()));
+                keeper => rule!(require(admin_badge.resource_address()));
+            })
+            // Only check_price_input_with_royalty carries a royalty, the admin can update it with
+            // the royalty module of the component.
+            .enable_component_royalties(component_royalties! {
+                roles {
+                    royalty_setter => rule!(require(admin_badge.resource_address()));
+                    royalty_setter_updater => rule!(deny_all);
+                    royalty_locker => rule!(require(admin_badge.resource_address()));
+                    royalty_locker_updater => rule!(deny_all);
+                    royalty_claimer => rule!(require(admin_badge.resource_address()));
+                    royalty_claimer_updater => rule!(deny_all);
+                },
+                init {
+                    update_subscription_pub_key => Free, updatable;
+                    set_subscription_rate_limit => Free, updatable;
+                    new_subscription => Free, updatable;
+                    new_trial_subscription => Free, updatable;
+                    new_metered_subscription => Free, updatable;
+                    mint_subscription_delegate => Free, updatable;
+                    revoke_subscription_delegate => Free, updatable;
+                    get_subscription_delegate => Free, updatable;
+                    renew_subscription => Free, updatable;
+                    export_subscription => Free, updatable;
+                    import_subscription => Free, updatable;
+                    top_up_subscription_credit => Free, updatable;
+                    withdraw_subscription_credit => Free, updatable;
+                    get_subscription_credit => Free, updatable;
+                    process_renewals => Free, updatable;
+                    check_price_input => Free, updatable;
+                    check_price_input_with_royalty => check_price_royalty, updatable;
+                    check_emergency_price_input => Free, updatable;
+                    check_price_input_with_max_confidence => Free, updatable;
+                    verify_price_input => Free, updatable;
+                    check_basket_price_input => Free, updatable;
+                    get_twap => Free, updatable;
+                    get_config => Free, updatable;
+                    get_subscription => Free, updatable;
+                    is_nonce_used => Free, updatable;
+                    remaining_calls => Free, updatable;
+                    remaining_time => Free, updatable;
+                    request_price => Free, updatable;
+                    stake_signer => Free, updatable;
+                    unstake_signer => Free, updatable;
+                    withdraw_signer_stake => Free, updatable;
+                    report_equivocation => Free, updatable;
+                    get_equivocation_report => Free, updatable;
+                    fulfil => Free, updatable;
+                    collect_subscription_fees => Free, updatable;
+                    set_subscription_cur_nonce => Free, updatable;
+                    grant_complimentary_subscription => Free, updatable;
+                    suspend_subscription => Free, updatable;
+                    reinstate_subscription => Free, updatable;
+                    set_price_deviation_guard => Free, updatable;
+                    set_emergency_pub_key => Free, updatable;
+                    set_migration_pub_key => Free, updatable;
+                    add_signer => Free, updatable;
+                    remove_signer => Free, updatable;
+                    set_trial_config => Free, updatable;
+                    set_grace_period => Free, updatable;
+                    set_subscription_image_url => Free, updatable;
+                    set_metered_billing => Free, updatable;
+                    set_signer_staking => Free, updatable;
+                    approve_staked_signer => Free, updatable;
+                    slash_signer => Free, updatable;
+                    slash_signer_for_wrong_price => Free, updatable;
+                    set_disable_equivocating_signers => Free, updatable;
+                }
             })
             .with_address(address_reservation)
             .metadata(metadata!(roles {
-            metadata_setter => rule!(deny_all);
-            metadata_setter_updater => rule!(deny_all);
-            metadata_locker => rule!(deny_all);
-            metadata_locker_updater => rule!(deny_all);
+            metadata_setter => OWNER;
+            metadata_setter_updater => OWNER;
+            metadata_locker => OWNER;
+            metadata_locker_updater => OWNER;
             },
             init {
                     "dapp_definition" => GlobalAddress::from(dapp_definition), updatable;
@@ -180,14 +688,44 @@ mod morpher_oracle {
             token_proof: NonFungibleProof,
         ) {
             let checked_proof = token_proof.check(self.subscription_manager.address());
-            info!("Local ID: {}", &checked_proof.as_non_fungible().non_fungible_local_id());
+            info!("Local ID: {}", &checked_proof.non_fungible_local_id());
             self.subscription_manager.update_non_fungible_data(
-                &checked_proof.as_non_fungible().non_fungible_local_id(),
+                &checked_proof.non_fungible_local_id(),
                 "authorized_pub_key",
                 new_public_key.to_string(),
             );
         }
 
+        /// Sets the rate limit of a subscription, so that a leaked key cannot use up all its calls.
+        ///
+        /// # Arguments
+        ///
+        /// * `subscription_proof`: proof of the subscription.
+        /// * `rate_limit`: maximum number of calls and window in seconds, `None` removes the limit.
+        ///   The window is at least `MIN_RATE_LIMIT_WINDOW` seconds.
+        pub fn set_subscription_rate_limit(
+            &mut self,
+            subscription_proof: NonFungibleProof,
+            rate_limit: Option<(u64, u64)>,
+        ) {
+            let checked_proof = subscription_proof.check(self.subscription_manager.address());
+
+            let rate_limit = rate_limit.map(|(max_calls, window)| {
+                assert!(
+                    max_calls > 0 && window >= MIN_RATE_LIMIT_WINDOW,
+                    "{}",
+                    OracleError::InvalidRateLimit
+                );
+                RateLimit::new(max_calls, window, get_time())
+            });
+
+            self.subscription_manager.update_non_fungible_data(
+                &checked_proof.non_fungible_local_id(),
+                "rate_limit",
+                rate_limit,
+            );
+        }
+
         /// Creates a new subscription.
         ///
         /// # Arguments
@@ -197,156 +735,1470 @@ mod morpher_oracle {
         ///
         /// # Returns
         ///
-        /// A new subscription soulbound NFT and the remaining tokens.
+        /// The remaining tokens, a new subscription soulbound NFT and its id.
         pub fn new_subscription(
             &mut self,
             months: u64,
             payment: FungibleBucket,
-        ) -> (FungibleBucket, NonFungibleBucket) {
+        ) -> (FungibleBucket, NonFungibleBucket, NonFungibleLocalId) {
             // First check that the payment is ok
             let remaining_tokens = self.check_payment(months, payment);
 
             // If it's ok, then mint a new subscription with the correct data
-            let expiration_time = get_time() + SECONDS_IN_A_MONTH * (months);
-            let subscription_data = OracleSubscription {
-                expiration_time,
-                cur_nonce: 0,
-                max_nonce: (API_CALLS_PER_MONTH * months),
-                authorized_pub_key: "".to_string(),
-            };
-
-            let subscription = self
-                .subscription_manager
-                .mint_ruid_non_fungible(subscription_data)
-                .as_non_fungible();
-
-            let ids = subscription.non_fungibles::<OracleSubscription>();
-            let id = ids.first().unwrap().local_id();
-
-            Runtime::emit_event(OracleSubscriptionUpdate {
-                new_expiration_time: expiration_time,
-                nft_id: id.clone(),
-            });
+            let subscription = self.mint_subscription(
+                get_time() + SECONDS_IN_A_MONTH * (months),
+                API_CALLS_PER_MONTH * months,
+                false,
+                false,
+                BillingMode::Monthly,
+            );
+            let subscription_id = subscription.non_fungible_local_id();
 
-            (remaining_tokens, subscription)
+            (remaining_tokens, subscription, subscription_id)
         }
 
-        /// Renews a given subscription by adding a set amount of months to it.
+        /// Creates a free trial subscription, limited to one per account.
+        ///
+        /// The number of trial calls is stored as the `max_nonce` of the subscription. Like for
+        /// paid subscriptions, the calls are counted by the oracle backend and synced with
+        /// `set_subscription_cur_nonce`, so this quota is only enforced once the backend synced it.
         ///
         /// # Arguments
         ///
-        /// - `subscription_proof`: Proof of the current subscription.
-        /// - `months`: amount of months of subscription to add.
-        /// - `payment`: An XRD bucket containing at least the payment for the desired month subscription.
+        /// * `account_badge`: proof of the owner badge of the account claiming the trial.
         ///
         /// # Returns
-        /// The remaining tokens.
-        pub fn renew_subscription(
-            &mut self,
-            subscription_id: NonFungibleLocalId,
-            months: u64,
-            payment: FungibleBucket,
-        ) -> FungibleBucket {
-            let subscription_data: OracleSubscription = self
-                .subscription_manager
-                .get_non_fungible_data(&subscription_id);
+        ///
+        /// A new trial subscription NFT.
+        pub fn new_trial_subscription(&mut self, account_badge: NonFungibleProof) -> NonFungibleBucket {
+            assert!(self.trial_days > 0, "{}", OracleError::TrialsNotAvailable);
 
-            let remaining_tokens = self.check_payment(months, payment);
+            let checked_proof = account_badge.check(ACCOUNT_OWNER_BADGE);
+            let account_badge_id = checked_proof.non_fungible_local_id();
 
-            let new_subscription_end = max(get_time(), subscription_data.expiration_time)
-                + SECONDS_IN_A_MONTH * (months);
+            assert!(
+                self.claimed_trials.get(&account_badge_id).is_none(),
+                "{}",
+                OracleError::TrialAlreadyClaimed
+            );
 
-            self.subscription_manager.update_non_fungible_data(
-                &subscription_id,
-                "expiration_time",
-                new_subscription_end,
+            let subscription = self.mint_subscription(
+                get_time() + self.trial_days * SECONDS_IN_A_DAY,
+                self.trial_calls,
+                true,
+                false,
+                BillingMode::Monthly,
             );
 
-            self.subscription_manager.update_non_fungible_data(
-                &subscription_id,
-                "max_nonce",
-                subscription_data.max_nonce + (API_CALLS_PER_MONTH * months),
+            self.claimed_trials
+                .insert(account_badge_id, subscription.non_fungible_local_id());
+
+            subscription
+        }
+
+        /// Creates a subscription which pays every call out of its credit instead of by the month.
+        ///
+        /// # Arguments
+        ///
+        /// * `payment`: XRD deposited as the initial credit, it can be topped up with
+        ///   `top_up_subscription_credit`.
+        ///
+        /// # Returns
+        ///
+        /// A new pay per call subscription NFT.
+        pub fn new_metered_subscription(&mut self, payment: FungibleBucket) -> NonFungibleBucket {
+            assert!(
+                self.price_per_call.is_some(),
+                "{}",
+                OracleError::MeteredBillingUnavailable
+            );
+            assert!(
+                payment.resource_address() == XRD,
+                "{}",
+                OracleError::PaymentNotInXrd
             );
 
-            Runtime::emit_event(OracleSubscriptionUpdate {
-                new_expiration_time: new_subscription_end,
-                nft_id: subscription_id,
-            });
+            let subscription =
+                self.mint_subscription(u64::MAX, u64::MAX, false, false, BillingMode::PayPerCall);
 
-            remaining_tokens
+            self.subscription_credits.insert(
+                subscription.non_fungible_local_id(),
+                FungibleVault::with_bucket(payment),
+            );
+
+            subscription
         }
 
-        pub fn set_subscription_cur_nonce(
+        /// Mints a delegate badge calling on behalf of a subscription, e.g. for one of several
+        /// components run by the same organisation.
+        ///
+        /// The calls of the delegate are taken out of the remaining calls of the subscription.
+        ///
+        /// # Arguments
+        ///
+        /// * `subscription_proof`: proof of the parent subscription.
+        /// * `max_calls`: number of calls the delegate can make.
+        /// * `expiration_time`: time after which the delegate stops working, at most the expiration
+        ///   of the subscription.
+        ///
+        /// # Returns
+        /// The delegate badge.
+        pub fn mint_subscription_delegate(
             &mut self,
-            subscription_id: NonFungibleLocalId,
-            oracle_request_msg: String,
-            signature: String,
-        ) {
-            let subscription_data: OracleSubscription = self
+            subscription_proof: NonFungibleProof,
+            max_calls: u64,
+            expiration_time: u64,
+        ) -> NonFungibleBucket {
+            let checked_proof = subscription_proof.check(self.subscription_manager.address());
+            let parent_id = checked_proof.non_fungible_local_id();
+            let parent: OracleSubscription = self
                 .subscription_manager
-                .get_non_fungible_data(&subscription_id);
-
-            //todo: check signature matching pub_key so that its a non-custodial update (subscription_id, etc etc)
-            let oracle_request_message =
-                OracleRequestMessage::from_str(&oracle_request_msg).unwrap();
-            check_signature(&oracle_request_msg, &signature, self.authorized_pub_key);
+                .get_non_fungible_data(&parent_id);
 
             assert!(
-                subscription_data.cur_nonce <= oracle_request_message.nonce,
-                "The new nonce must be bigger than the previous nonce"
+                parent.billing_mode == BillingMode::Monthly,
+                "{}",
+                OracleError::DelegationNotSupported
+            );
+            assert!(
+                get_time() <= parent.expiration_time,
+                "{}",
+                OracleError::SubscriptionExpired
+            );
+            assert!(
+                expiration_time <= parent.expiration_time,
+                "{}",
+                OracleError::DelegateOutlivesSubscription
+            );
+            assert!(
+                parent.max_nonce.saturating_sub(parent.cur_nonce) >= max_calls,
+                "{}",
+                OracleError::NoCallsLeft
             );
 
             self.subscription_manager.update_non_fungible_data(
-                &subscription_id,
-                "cur_nonce",
-                oracle_request_message.nonce,
+                &parent_id,
+                "max_nonce",
+                parent.max_nonce - max_calls,
             );
-        }
-
-        pub fn check_price_input(
-            &mut self,
-            message: String,
-            signature: String,
-        ) -> PriceMessage {
 
-            // Then check the message is correct
-            check_signature(&message, &signature, self.authorized_pub_key);
+            let delegate = self
+                .delegate_manager
+                .mint_ruid_non_fungible(SubscriptionDelegate {
+                    parent_id: parent_id.clone(),
+                    max_calls,
+                    expiration_time,
+                    calls_used: 0,
+                    revoked: false,
+                });
 
-            // If everything is fine, parse the message
-            let price_message = PriceMessage::from_str(&message).unwrap();
+            let active_delegates = self
+                .active_delegates
+                .get(&parent_id)
+                .map_or(0, |count| *count);
+            self.active_delegates
+                .insert(parent_id.clone(), active_delegates + 1);
 
-            // Check that the nonce has not been used
-            assert!(
-                self.used_nonce.insert(price_message.nonce),
-                "This nonce has already been used"
-            );
+            Runtime::emit_event(SubscriptionDelegateMinted {
+                parent_id,
+                delegate_id: delegate.non_fungible_local_id(),
+                max_calls,
+                expiration_time,
+            });
 
-            price_message
+            delegate
         }
 
-        /// Collects subscription fees.
+        /// Revokes a delegate badge and gives its unused calls back to the subscription.
         ///
-        /// # Returns
-        /// All paid subscription fees.
-        pub fn collect_subscription_fees(&mut self) -> FungibleBucket {
-            self.subscription_fees_vault.take_all()
-        }
-
-        fn check_payment(&mut self, months: u64, mut payment: FungibleBucket) -> FungibleBucket {
-            assert!(months > 0, "Cannot add 0 months to the subscription!");
-            assert_eq!(
-                payment.resource_address(),
-                XRD,
-                "The payment should be made in XRD tokens!"
-            );
+        /// # Arguments
+        ///
+        /// * `subscription_proof`: proof of the parent subscription.
+        /// * `delegate_id`: the delegate to revoke.
+        pub fn revoke_subscription_delegate(
+            &mut self,
+            subscription_proof: NonFungibleProof,
+            delegate_id: NonFungibleLocalId,
+        ) {
+            let checked_proof = subscription_proof.check(self.subscription_manager.address());
+            let parent_id = checked_proof.non_fungible_local_id();
+            let delegate: SubscriptionDelegate =
+                self.delegate_manager.get_non_fungible_data(&delegate_id);
 
-            let expected_payment = months * self.monthly_subscription_fee;
             assert!(
-                expected_payment <= payment.amount(),
-                "For {} months, the expected payment is {} XRD (only supplied {})",
-                months,
-                expected_payment,
-                payment.amount()
+                delegate.parent_id == parent_id,
+                "{}",
+                OracleError::DelegateNotOwned
+            );
+            assert!(!delegate.revoked, "{}", OracleError::DelegateRevoked);
+
+            self.delegate_manager
+                .update_non_fungible_data(&delegate_id, "revoked", true);
+            *self.active_delegates.get_mut(&parent_id).unwrap() -= 1;
+
+            let returned_calls = delegate.max_calls - delegate.calls_used;
+            let parent: OracleSubscription = self
+                .subscription_manager
+                .get_non_fungible_data(&parent_id);
+            self.subscription_manager.update_non_fungible_data(
+                &parent_id,
+                "max_nonce",
+                parent.max_nonce + returned_calls,
+            );
+
+            Runtime::emit_event(SubscriptionDelegateRevoked {
+                parent_id,
+                delegate_id,
+                returned_calls,
+            });
+        }
+
+        /// Returns the data of a delegate badge.
+        pub fn get_subscription_delegate(
+            &self,
+            delegate_id: NonFungibleLocalId,
+        ) -> SubscriptionDelegate {
+            self.delegate_manager.get_non_fungible_data(&delegate_id)
+        }
+
+        /// Renews a given subscription by adding a set amount of months to it.
+        ///
+        /// # Arguments
+        ///
+        /// - `subscription_proof`: Proof of the current subscription.
+        /// - `months`: amount of months of subscription to add.
+        /// - `payment`: An XRD bucket containing at least the payment for the desired month subscription.
+        ///
+        /// # Returns
+        /// The remaining tokens.
+        pub fn renew_subscription(
+            &mut self,
+            subscription_id: NonFungibleLocalId,
+            months: u64,
+            payment: FungibleBucket,
+        ) -> FungibleBucket {
+            let subscription_data: OracleSubscription = self
+                .subscription_manager
+                .get_non_fungible_data(&subscription_id);
+            assert!(
+                subscription_data.billing_mode == BillingMode::Monthly,
+                "{}",
+                OracleError::PayPerCallSubscription
+            );
+
+            let remaining_tokens = self.check_payment(months, payment);
+
+            self.extend_subscription(&subscription_id, months);
+
+            remaining_tokens
+        }
+
+        /// Burns a subscription so that it can be moved to another oracle deployment.
+        ///
+        /// The emitted `OracleSubscriptionExported` event contains the receipt to be signed with the
+        /// migration key and passed to `import_subscription` of the new deployment. The delegates
+        /// of the subscription have to be revoked first, so that their calls are part of the
+        /// receipt.
+        ///
+        /// # Arguments
+        ///
+        /// * `subscription`: the subscription to export.
+        /// * `target`: the oracle deployment allowed to import the subscription.
+        /// * `recipient`: the account receiving the imported subscription.
+        ///
+        /// # Returns
+        /// The remaining credit of the subscription, if it was ever credited.
+        pub fn export_subscription(
+            &mut self,
+            subscription: NonFungibleBucket,
+            target: ComponentAddress,
+            recipient: ComponentAddress,
+        ) -> Option<FungibleBucket> {
+            assert!(
+                subscription.resource_address() == self.subscription_manager.address(),
+                "{}",
+                OracleError::InvalidSubscriptionId
+            );
+
+            let subscription_id = subscription.non_fungible_local_id();
+            let subscription_data: OracleSubscription = self
+                .subscription_manager
+                .get_non_fungible_data(&subscription_id);
+
+            // Moving to another deployment must not lift a suspension
+            assert!(
+                !subscription_data.suspended,
+                "{}",
+                OracleError::SubscriptionSuspended
+            );
+            assert!(
+                self.active_delegates
+                    .get(&subscription_id)
+                    .is_none_or(|count| *count == 0),
+                "{}",
+                OracleError::ActiveDelegates
+            );
+
+            let now = get_time();
+            assert!(
+                now <= subscription_data.expiration_time,
+                "{}",
+                OracleError::SubscriptionExpired
+            );
+
+            let receipt = MigrationReceipt {
+                source: Runtime::bech32_encode_address(Runtime::global_address()),
+                target: Runtime::bech32_encode_address(target),
+                recipient: Runtime::bech32_encode_address(recipient),
+                subscription_id: subscription_id.to_string(),
+                remaining_time: subscription_data.expiration_time - now,
+                remaining_calls: subscription_data
+                    .max_nonce
+                    .saturating_sub(subscription_data.cur_nonce),
+                authorized_pub_key: subscription_data.authorized_pub_key,
+                exported_at: now,
+                trial: subscription_data.trial,
+                complimentary: subscription_data.complimentary,
+                billing_mode: subscription_data.billing_mode,
+                rate_limit: subscription_data
+                    .rate_limit
+                    .map(|rate_limit| (rate_limit.max_calls, rate_limit.window)),
+            };
+
+            let credit = self
+                .subscription_credits
+                .get_mut(&subscription_id)
+                .map(|mut credit| credit.take_all());
+
+            subscription.burn();
+
+            Runtime::emit_event(OracleSubscriptionExported {
+                nft_id: subscription_id,
+                receipt: receipt.to_string(),
+            });
+
+            credit
+        }
+
+        /// Mints a subscription exported from another oracle deployment and deposits it into the
+        /// recipient of the receipt.
+        ///
+        /// The subscription keeps the expiration time of the exported one, the receipt has to be
+        /// imported within `max_migration_receipt_age` of its export.
+        ///
+        /// # Arguments
+        ///
+        /// * `receipt`: the receipt of the `OracleSubscriptionExported` event.
+        /// * `signature`: the signature of the receipt by the migration key.
+        /// * `recipient`: the account named in the receipt.
+        pub fn import_subscription(
+            &mut self,
+            receipt: String,
+            signature: String,
+            recipient: ComponentAddress,
+        ) {
+            let migration_pub_key = self
+                .migration_pub_key
+                .as_ref()
+                .unwrap_or_else(|| panic!("{}", OracleError::NoMigrationKey));
+            check_signature(&receipt, &signature, migration_pub_key);
+
+            let migration_receipt =
+                MigrationReceipt::from_str(&receipt).unwrap_or_else(|err| panic!("{}", err));
+            assert!(
+                migration_receipt.target
+                    == Runtime::bech32_encode_address(Runtime::global_address()),
+                "{}",
+                OracleError::MigrationTargetMismatch
+            );
+            assert!(
+                migration_receipt.recipient == Runtime::bech32_encode_address(recipient),
+                "{}",
+                OracleError::MigrationRecipientMismatch
+            );
+            assert!(
+                get_time()
+                    <= migration_receipt
+                        .exported_at
+                        .saturating_add(self.max_migration_receipt_age),
+                "{}",
+                OracleError::MigrationReceiptTooOld
+            );
+
+            let import_key = (
+                migration_receipt.source.clone(),
+                migration_receipt.subscription_id.clone(),
+            );
+            assert!(
+                self.imported_subscriptions.get(&import_key).is_none(),
+                "{}",
+                OracleError::SubscriptionAlreadyImported
+            );
+
+            // Pay per call subscriptions never expire
+            let subscription = self.mint_subscription(
+                migration_receipt
+                    .exported_at
+                    .saturating_add(migration_receipt.remaining_time),
+                migration_receipt.remaining_calls,
+                migration_receipt.trial,
+                migration_receipt.complimentary,
+                migration_receipt.billing_mode,
+            );
+            let subscription_id = subscription.non_fungible_local_id();
+
+            self.subscription_manager.update_non_fungible_data(
+                &subscription_id,
+                "authorized_pub_key",
+                migration_receipt.authorized_pub_key,
+            );
+            if let Some((max_calls, window)) = migration_receipt.rate_limit {
+                self.subscription_manager.update_non_fungible_data(
+                    &subscription_id,
+                    "rate_limit",
+                    Some(RateLimit::new(max_calls, window, get_time())),
+                );
+            }
+            self.imported_subscriptions
+                .insert(import_key, subscription_id.clone());
+
+            Runtime::emit_event(OracleSubscriptionImported {
+                nft_id: subscription_id,
+                source: migration_receipt.source,
+                source_subscription_id: migration_receipt.subscription_id,
+            });
+
+            let mut recipient: Global<Account> = recipient.into();
+            recipient.try_deposit_or_abort(subscription.into(), None);
+        }
+
+        /// Deposits XRD into the credit of a subscription.
+        ///
+        /// Once the subscription expires, the next call or `process_renewals` renews it for a month
+        /// out of its credit.
+        ///
+        /// # Arguments
+        ///
+        /// * `subscription_id`: the subscription to credit, anyone can top up any subscription.
+        /// * `payment`: the XRD to deposit.
+        pub fn top_up_subscription_credit(
+            &mut self,
+            subscription_id: NonFungibleLocalId,
+            payment: FungibleBucket,
+        ) {
+            assert!(
+                payment.resource_address() == XRD,
+                "{}",
+                OracleError::PaymentNotInXrd
+            );
+
+            // Fails if the subscription does not exist
+            let _: OracleSubscription = self
+                .subscription_manager
+                .get_non_fungible_data(&subscription_id);
+
+            let payment = match self.subscription_credits.get_mut(&subscription_id) {
+                Some(mut credit) => {
+                    credit.put(payment);
+                    return;
+                }
+                None => payment,
+            };
+
+            self.subscription_credits
+                .insert(subscription_id, FungibleVault::with_bucket(payment));
+        }
+
+        /// Withdraws XRD from the credit of a subscription.
+        ///
+        /// # Arguments
+        ///
+        /// * `subscription_proof`: proof of the credited subscription.
+        /// * `amount`: XRD to withdraw, at most the credit of the subscription.
+        pub fn withdraw_subscription_credit(
+            &mut self,
+            subscription_proof: NonFungibleProof,
+            amount: Decimal,
+        ) -> FungibleBucket {
+            let checked_proof = subscription_proof.check(self.subscription_manager.address());
+            let subscription_id = checked_proof.non_fungible_local_id();
+
+            let mut credit = self
+                .subscription_credits
+                .get_mut(&subscription_id)
+                .unwrap_or_else(|| panic!("{}", OracleError::NoSubscriptionCredit));
+            assert!(
+                amount <= credit.amount(),
+                "{}",
+                OracleError::CreditWithdrawalTooLarge
+            );
+
+            credit.take(amount)
+        }
+
+        /// Returns the XRD credit of a subscription.
+        pub fn get_subscription_credit(&self, subscription_id: NonFungibleLocalId) -> Decimal {
+            self.subscription_credits
+                .get(&subscription_id)
+                .map_or(Decimal::ZERO, |credit| credit.amount())
+        }
+
+        /// Renews the given expired subscriptions for a month out of their credit.
+        ///
+        /// Subscriptions which are not expired or do not have enough credit are skipped.
+        ///
+        /// # Returns
+        /// The renewed subscriptions.
+        pub fn process_renewals(
+            &mut self,
+            subscription_ids: Vec<NonFungibleLocalId>,
+        ) -> Vec<NonFungibleLocalId> {
+            let now = get_time();
+
+            subscription_ids
+                .into_iter()
+                .filter(|subscription_id| {
+                    let subscription: OracleSubscription = self
+                        .subscription_manager
+                        .get_non_fungible_data(subscription_id);
+
+                    now > subscription.expiration_time && self.renew_from_credit(subscription_id)
+                })
+                .collect()
+        }
+
+        /// Syncs the number of calls counted by the oracle backend into the subscription.
+        ///
+        /// The backend is the only source of the call count, on-chain calls only check it against
+        /// `max_nonce`. Suspended subscriptions are synced too, so that the count is right once they
+        /// are reinstated.
+        pub fn set_subscription_cur_nonce(
+            &mut self,
+            subscription_id: NonFungibleLocalId,
+            oracle_request_msg: String,
+            signature: String,
+        ) {
+            let subscription_data: OracleSubscription = self
+                .subscription_manager
+                .get_non_fungible_data(&subscription_id);
+
+            //todo: check signature matching pub_key so that its a non-custodial update (subscription_id, etc etc)
+            let oracle_request_message =
+                OracleRequestMessage::from_str(&oracle_request_msg)
+                    .unwrap_or_else(|err| panic!("{}", err));
+            self.check_signer_signature(&oracle_request_msg, &signature);
+
+            assert!(
+                subscription_data.cur_nonce <= oracle_request_message.nonce,
+                "{}",
+                OracleError::NonceNotIncreasing
+            );
+
+            self.subscription_manager.update_non_fungible_data(
+                &subscription_id,
+                "cur_nonce",
+                oracle_request_message.nonce,
+            );
+        }
+
+        /// Checks a signed price message and counts the call against the subscription.
+        ///
+        /// # Arguments
+        ///
+        /// * `message`: the price message.
+        /// * `signature`: the oracle signature of the message.
+        /// * `subscription_proof`: proof of the subscription paying for the call.
+        ///
+        /// # Returns
+        /// The verified price message.
+        pub fn check_price_input(
+            &mut self,
+            message: String,
+            signature: String,
+            subscription_proof: NonFungibleProof,
+        ) -> PriceMessage {
+            self.use_subscription(subscription_proof);
+            self.accept_price_input(message, signature, None)
+        }
+
+        /// Checks a signed price message without a subscription, paid by the royalty of this method.
+        ///
+        /// # Arguments
+        ///
+        /// * `message`: the price message.
+        /// * `signature`: the oracle signature of the message.
+        ///
+        /// # Returns
+        /// The verified price message.
+        pub fn check_price_input_with_royalty(
+            &mut self,
+            message: String,
+            signature: String,
+        ) -> PriceMessage {
+            self.accept_price_input(message, signature, None)
+        }
+
+        /// Verifies a price message without consuming its nonce or counting the call.
+        ///
+        /// This is meant for quotes only (e.g. showing what a purchase would cost): the same message
+        /// can be verified any number of times, so it is NOT safe to settle anything against the
+        /// returned price. Use `check_price_input` for settlement.
+        ///
+        /// # Arguments
+        ///
+        /// * `message`: the price message.
+        /// * `signature`: the oracle signature of the message.
+        /// * `subscription_proof`: proof of a subscription which can still make calls.
+        ///
+        /// # Returns
+        /// The verified price message.
+        pub fn verify_price_input(
+            &self,
+            message: String,
+            signature: String,
+            subscription_proof: NonFungibleProof,
+        ) -> PriceMessage {
+            let checked_proof = subscription_proof.check(self.subscription_manager.address());
+            let subscription_id = checked_proof.non_fungible_local_id();
+            let subscription: OracleSubscription = self
+                .subscription_manager
+                .get_non_fungible_data(&subscription_id);
+
+            assert!(
+                !subscription.suspended,
+                "{}",
+                OracleError::SubscriptionSuspended
+            );
+
+            if self.subscription_remaining_calls(&subscription_id, &subscription) == 0 {
+                if get_time() <= subscription.expiration_time {
+                    panic!("{}", OracleError::NoCallsLeft);
+                } else {
+                    panic!("{}", OracleError::SubscriptionExpired);
+                }
+            }
+
+            let price_message = self.validate_price_input(&message, &signature);
+            self.check_price_deviation(
+                &price_message.market_id,
+                price_message.price,
+                price_message.created_at,
+            );
+
+            price_message
+        }
+
+        /// Checks a signed basket of prices and counts a single call against the subscription.
+        ///
+        /// # Arguments
+        ///
+        /// * `message`: the basket price message.
+        /// * `signature`: the oracle signature of the message.
+        /// * `subscription_proof`: proof of the subscription paying for the call.
+        ///
+        /// # Returns
+        /// The price of every market in the basket.
+        pub fn check_basket_price_input(
+            &mut self,
+            message: String,
+            signature: String,
+            subscription_proof: NonFungibleProof,
+        ) -> HashMap<String, Decimal> {
+            self.use_subscription(subscription_proof);
+
+            self.check_signer_signature(&message, &signature);
+
+            let basket_price_message =
+                BasketPriceMessage::from_str(&message).unwrap_or_else(|err| panic!("{}", err));
+
+            check_validity_window(
+                basket_price_message.created_at,
+                basket_price_message.valid_until,
+            );
+
+            // The basket shares its nonces with single price messages
+            assert!(
+                self.used_nonce.insert(basket_price_message.nonce),
+                "{}",
+                OracleError::NonceAlreadyUsed
+            );
+
+            let mut prices = HashMap::new();
+            for (market_id, price) in basket_price_message.prices {
+                self.check_price_deviation(&market_id, price, basket_price_message.created_at);
+                self.record_price(&market_id, price, basket_price_message.created_at);
+                prices.insert(market_id, price);
+            }
+
+            prices
+        }
+
+        /// Checks a price message and additionally requires its confidence interval to be at most
+        /// `max_confidence` wide.
+        pub fn check_price_input_with_max_confidence(
+            &mut self,
+            message: String,
+            signature: String,
+            max_confidence: Decimal,
+            subscription_proof: NonFungibleProof,
+        ) -> PriceMessage {
+            self.use_subscription(subscription_proof);
+            let price_message = self.accept_price_input(message, signature, None);
+
+            assert!(
+                price_message.confidence <= max_confidence,
+                "{}",
+                OracleError::ConfidenceTooWide {
+                    confidence: price_message.confidence,
+                    max_confidence,
+                }
+            );
+
+            price_message
+        }
+
+        /// Checks a price message that is additionally co-signed by the emergency key.
+        ///
+        /// Co-signed messages bypass the price deviation guard, so that a genuine large move can be
+        /// accepted and becomes the new reference price of the market.
+        pub fn check_emergency_price_input(
+            &mut self,
+            message: String,
+            signature: String,
+            emergency_signature: String,
+            subscription_proof: NonFungibleProof,
+        ) -> PriceMessage {
+            self.use_subscription(subscription_proof);
+            self.accept_price_input(message, signature, Some(emergency_signature))
+        }
+
+        /// Returns the time weighted average price of a market.
+        ///
+        /// # Arguments
+        ///
+        /// * `market_id`: the market to get the TWAP for.
+        /// * `window_seconds`: length of the averaging window, ending now.
+        ///
+        /// # Returns
+        /// The TWAP over the window, fails if no accepted price exists before the start of the window.
+        pub fn get_twap(&self, market_id: String, window_seconds: u64) -> Decimal {
+            self.twap_accumulators
+                .get(&market_id)
+                .and_then(|accumulator| accumulator.twap(get_time(), window_seconds))
+                .unwrap_or_else(|| panic!("{}", OracleError::NotEnoughTwapObservations))
+        }
+
+        /// Requests a price to be delivered on-chain by the oracle keeper.
+        ///
+        /// Once the keeper fulfils the request, `callback_method` is called on `callback_component`
+        /// with the request id and the verified `PriceMessage`. Anyone can name any component as
+        /// callback, so `global_caller` of this component only proves the call comes from the oracle.
+        /// The callback must also check that the request id is one it requested itself, for the
+        /// market it expects, and accept it only once.
+        ///
+        /// # Arguments
+        ///
+        /// * `market_id`: the market to get the price for.
+        /// * `callback_component`: the component receiving the price.
+        /// * `callback_method`: the method called with `(u64, PriceMessage)`.
+        /// * `subscription_proof`: proof of the subscription paying for the call.
+        ///
+        /// # Returns
+        /// The id of the pending request.
+        pub fn request_price(
+            &mut self,
+            market_id: String,
+            callback_component: ComponentAddress,
+            callback_method: String,
+            subscription_proof: NonFungibleProof,
+        ) -> u64 {
+            self.use_subscription(subscription_proof);
+
+            let request_id = self.next_price_request_id;
+            self.next_price_request_id += 1;
+
+            self.price_requests.insert(
+                request_id,
+                PriceRequest {
+                    market_id: market_id.clone(),
+                    callback_component,
+                    callback_method: callback_method.clone(),
+                    requested_at: get_time(),
+                },
+            );
+
+            Runtime::emit_event(PriceRequested {
+                request_id,
+                market_id,
+                callback_component,
+                callback_method,
+            });
+
+            request_id
+        }
+
+        /// Fulfils a pending price request and calls back the requesting component.
+        ///
+        /// # Arguments
+        ///
+        /// * `request_id`: the id returned by `request_price`.
+        /// * `message`: the signed price message for the requested market.
+        /// * `signature`: the oracle signature of the message.
+        pub fn fulfil(&mut self, request_id: u64, message: String, signature: String) {
+            let request = self
+                .price_requests
+                .remove(&request_id)
+                .unwrap_or_else(|| panic!("{}", OracleError::PriceRequestNotFound));
+
+            let price_message = self.accept_price_input(message, signature, None);
+
+            assert!(
+                price_message.market_id == request.market_id,
+                "{}",
+                OracleError::MarketMismatch
+            );
+
+            Runtime::emit_event(PriceRequestFulfilled {
+                request_id,
+                market_id: price_message.market_id.clone(),
+                price: price_message.price,
+            });
+
+            let callback_component: Global<AnyComponent> = request.callback_component.into();
+            callback_component.call_raw::<()>(
+                &request.callback_method,
+                scrypto_args!(request_id, price_message),
+            );
+        }
+
+        /// Stakes XRD for a key to join the signer set.
+        ///
+        /// The key only signs prices once the admin approved it with `approve_staked_signer`, until
+        /// then its stake can already be slashed.
+        ///
+        /// # Arguments
+        ///
+        /// * `key_type`: signature scheme of the key.
+        /// * `public_key`: hex encoded public key.
+        /// * `ownership_signature`: signature of `stake##<public_key>` by the key, proving it is held
+        ///   by the staker.
+        /// * `stake`: at least the minimum signer stake in XRD.
+        ///
+        /// # Returns
+        /// The stake NFT, needed to unstake.
+        pub fn stake_signer(
+            &mut self,
+            key_type: OracleKeyType,
+            public_key: String,
+            ownership_signature: String,
+            stake: FungibleBucket,
+        ) -> NonFungibleBucket {
+            let min_signer_stake = self
+                .min_signer_stake
+                .unwrap_or_else(|| panic!("{}", OracleError::StakingClosed));
+
+            assert!(
+                stake.resource_address() == XRD,
+                "{}",
+                OracleError::PaymentNotInXrd
+            );
+            assert!(
+                stake.amount() >= min_signer_stake,
+                "{}",
+                OracleError::InsufficientStake {
+                    minimum: min_signer_stake,
+                    supplied: stake.amount(),
+                }
+            );
+
+            let signer = OraclePublicKey::parse(key_type, public_key.as_str());
+            assert!(
+                !self.signers.contains(&signer) && !self.staked_signers.contains_key(&signer),
+                "{}",
+                OracleError::SignerAlreadyExists
+            );
+            assert!(
+                self.equivocating_signers.get(&signer).is_none(),
+                "{}",
+                OracleError::EquivocatingSigner
+            );
+            assert!(
+                self.migration_pub_key.as_ref() != Some(&signer),
+                "{}",
+                OracleError::StakedMigrationKey
+            );
+            check_signature(&format!("stake##{}", public_key), &ownership_signature, &signer);
+
+            let amount = stake.amount();
+            self.stake_vault.put(stake);
+
+            let stake_nft = self
+                .stake_manager
+                .mint_ruid_non_fungible(SignerStake {
+                    public_key: signer.clone(),
+                    amount,
+                    unbonding_until: None,
+                });
+            let stake_id = stake_nft.non_fungible_local_id();
+
+            self.staked_signers.insert(signer.clone(), stake_id.clone());
+
+            Runtime::emit_event(SignerStaked {
+                public_key: signer,
+                stake_id,
+                amount,
+            });
+
+            stake_nft
+        }
+
+        /// Adds a staked key to the signer set, its prices are accepted from then on.
+        pub fn approve_staked_signer(&mut self, key_type: OracleKeyType, public_key: String) {
+            let signer = OraclePublicKey::parse(key_type, public_key.as_str());
+            let stake_id = self
+                .staked_signers
+                .get(&signer)
+                .cloned()
+                .unwrap_or_else(|| panic!("{}", OracleError::SignerNotStaked));
+            let stake: SignerStake = self.stake_manager.get_non_fungible_data(&stake_id);
+
+            assert!(
+                stake.unbonding_until.is_none(),
+                "{}",
+                OracleError::AlreadyUnbonding
+            );
+            assert!(
+                !self.signers.contains(&signer),
+                "{}",
+                OracleError::SignerAlreadyExists
+            );
+
+            self.signers.push(signer.clone());
+
+            Runtime::emit_event(StakedSignerApproved {
+                public_key: signer,
+                stake_id,
+            });
+        }
+
+        /// Leaves the signer set and starts the unbonding delay of the stake.
+        ///
+        /// The stake can still be slashed for messages signed before leaving until it is withdrawn.
+        pub fn unstake_signer(&mut self, stake_proof: NonFungibleProof) {
+            let checked_proof = stake_proof.check(self.stake_manager.address());
+            let stake_id = checked_proof.non_fungible_local_id();
+            let stake: SignerStake = self.stake_manager.get_non_fungible_data(&stake_id);
+
+            assert!(
+                stake.unbonding_until.is_none(),
+                "{}",
+                OracleError::AlreadyUnbonding
+            );
+
+            // The key may already have been removed by the admin
+            if let Some(index) = self.signers.iter().position(|key| *key == stake.public_key) {
+                assert!(self.signers.len() > 1, "{}", OracleError::LastSigner);
+                self.signers.remove(index);
+            }
+
+            let unbonding_until = get_time() + self.unbonding_delay;
+            self.stake_manager.update_non_fungible_data(
+                &stake_id,
+                "unbonding_until",
+                Some(unbonding_until),
+            );
+
+            Runtime::emit_event(SignerUnbonding {
+                public_key: stake.public_key,
+                stake_id,
+                unbonding_until,
+            });
+        }
+
+        /// Withdraws an unbonded stake and burns its NFT.
+        ///
+        /// # Returns
+        /// The staked XRD, nothing if the stake was slashed.
+        pub fn withdraw_signer_stake(&mut self, stake_nft: NonFungibleBucket) -> FungibleBucket {
+            assert!(
+                stake_nft.resource_address() == self.stake_manager.address(),
+                "{}",
+                OracleError::InvalidStake
+            );
+
+            let stake_id = stake_nft.non_fungible_local_id();
+            let stake: SignerStake = self.stake_manager.get_non_fungible_data(&stake_id);
+
+            let unbonding_until = stake
+                .unbonding_until
+                .unwrap_or_else(|| panic!("{}", OracleError::StakeNotUnbonding));
+            assert!(
+                get_time() >= unbonding_until,
+                "{}",
+                OracleError::UnbondingNotFinished
+            );
+
+            if self.staked_signers.get(&stake.public_key) == Some(&stake_id) {
+                self.staked_signers.remove(&stake.public_key);
+            }
+            stake_nft.burn();
+
+            self.stake_vault.take(stake.amount)
+        }
+
+        /// Reports a signer which signed two different prices for the same market and nonce.
+        ///
+        /// Anyone can report. The offense is recorded once, and the key is removed from the signer
+        /// set if `disable_equivocating_signers` is enabled, unless it is the last signer. Slashing its
+        /// stake is left to the admin.
+        ///
+        /// # Arguments
+        ///
+        /// * `key_type`, `public_key`: the signer which signed both messages.
+        /// * `message_a`, `signature_a`: the first signed price message.
+        /// * `message_b`, `signature_b`: the conflicting signed price message.
+        pub fn report_equivocation(
+            &mut self,
+            key_type: OracleKeyType,
+            public_key: String,
+            message_a: String,
+            signature_a: String,
+            message_b: String,
+            signature_b: String,
+        ) {
+            let signer = OraclePublicKey::parse(key_type, public_key.as_str());
+            let (price_message_a, price_message_b) = self.check_equivocation(
+                &signer,
+                &message_a,
+                &signature_a,
+                &message_b,
+                &signature_b,
+            );
+
+            assert!(
+                self.record_equivocation(&signer, &price_message_a, &price_message_b),
+                "{}",
+                OracleError::EquivocationAlreadyReported
+            );
+
+            // The last signer is only flagged, anyone can report and must not stop the oracle
+            let signer_disabled = self.disable_equivocating_signers
+                && self.signers.contains(&signer)
+                && self.signers.len() > 1;
+            if signer_disabled {
+                self.signers.retain(|key| *key != signer);
+            }
+
+            Runtime::emit_event(EquivocationReported {
+                public_key: signer,
+                market_id: price_message_a.market_id,
+                nonce: price_message_a.nonce,
+                price_a: price_message_a.price,
+                price_b: price_message_b.price,
+                signer_disabled,
+            });
+        }
+
+        /// Returns the reported conflicting prices of a signer for a market and nonce, if any.
+        pub fn get_equivocation_report(
+            &self,
+            key_type: OracleKeyType,
+            public_key: String,
+            market_id: String,
+            nonce: u64,
+        ) -> Option<EquivocationReport> {
+            let signer = OraclePublicKey::parse(key_type, public_key.as_str());
+
+            self.equivocation_reports
+                .get(&(signer, market_id, nonce))
+                .map(|report| report.clone())
+        }
+
+        /// Returns the current configuration of the oracle.
+        pub fn get_config(&self) -> OracleConfig {
+            OracleConfig {
+                signers: self.signers.clone(),
+                emergency_pub_key: self.emergency_pub_key.clone(),
+                monthly_subscription_fee: self.monthly_subscription_fee,
+                subscription_resource: self.subscription_manager.address(),
+                subscription_image_url: self.subscription_image_url.clone(),
+                delegate_resource: self.delegate_manager.address(),
+                max_price_deviation: self.max_price_deviation,
+                price_deviation_window: self.price_deviation_window,
+                trial_days: self.trial_days,
+                trial_calls: self.trial_calls,
+                grace_period: self.grace_period,
+                grace_period_calls: self.grace_period_calls,
+                price_per_call: self.price_per_call,
+                low_credit_threshold: self.low_credit_threshold,
+                stake_resource: self.stake_manager.address(),
+                min_signer_stake: self.min_signer_stake,
+                unbonding_delay: self.unbonding_delay,
+                disable_equivocating_signers: self.disable_equivocating_signers,
+            }
+        }
+
+        /// Returns the data of a subscription together with its remaining time and calls.
+        pub fn get_subscription(&self, subscription_id: NonFungibleLocalId) -> SubscriptionInfo {
+            let subscription: OracleSubscription = self
+                .subscription_manager
+                .get_non_fungible_data(&subscription_id);
+
+            SubscriptionInfo {
+                remaining_time: subscription.expiration_time.saturating_sub(get_time()),
+                remaining_calls: self.subscription_remaining_calls(&subscription_id, &subscription),
+                credit: self.get_subscription_credit(subscription_id),
+                subscription,
+            }
+        }
+
+        /// Returns whether a price message with this nonce has already been accepted.
+        pub fn is_nonce_used(&self, nonce: u64) -> bool {
+            self.used_nonce.contains(&nonce)
+        }
+
+        /// Returns the number of calls a subscription can still make, including the grace period
+        /// once it expired.
+        pub fn remaining_calls(&self, subscription_id: NonFungibleLocalId) -> u64 {
+            let subscription: OracleSubscription = self
+                .subscription_manager
+                .get_non_fungible_data(&subscription_id);
+
+            self.subscription_remaining_calls(&subscription_id, &subscription)
+        }
+
+        /// Returns the number of seconds until a subscription expires, 0 once expired.
+        pub fn remaining_time(&self, subscription_id: NonFungibleLocalId) -> u64 {
+            let subscription: OracleSubscription = self
+                .subscription_manager
+                .get_non_fungible_data(&subscription_id);
+
+            subscription.expiration_time.saturating_sub(get_time())
+        }
+
+        /// Mints a free subscription, e.g. for partners and hackathon teams.
+        ///
+        /// # Arguments
+        ///
+        /// * `duration`: seconds until the subscription expires.
+        /// * `calls`: number of API calls included.
+        ///
+        /// # Returns
+        /// A new subscription NFT tagged as complimentary.
+        pub fn grant_complimentary_subscription(
+            &mut self,
+            duration: u64,
+            calls: u64,
+        ) -> NonFungibleBucket {
+            let expiration_time = get_time()
+                .checked_add(duration)
+                .unwrap_or_else(|| panic!("{}", OracleError::SubscriptionDurationOverflow));
+
+            self.mint_subscription(expiration_time, calls, false, true, BillingMode::Monthly)
+        }
+
+        /// Suspends a subscription, it cannot make calls until it is reinstated.
+        ///
+        /// # Arguments
+        ///
+        /// * `subscription_id`: the subscription to suspend.
+        /// * `reason`: why the subscription is suspended, visible to the subscriber.
+        pub fn suspend_subscription(&mut self, subscription_id: NonFungibleLocalId, reason: String) {
+            let subscription: OracleSubscription = self
+                .subscription_manager
+                .get_non_fungible_data(&subscription_id);
+            assert!(
+                !subscription.suspended,
+                "{}",
+                OracleError::SubscriptionSuspended
+            );
+
+            self.subscription_manager
+                .update_non_fungible_data(&subscription_id, "suspended", true);
+            self.subscription_manager.update_non_fungible_data(
+                &subscription_id,
+                "suspension_reason",
+                reason.clone(),
+            );
+
+            Runtime::emit_event(OracleSubscriptionSuspended {
+                nft_id: subscription_id,
+                reason,
+            });
+        }
+
+        /// Lifts the suspension of a subscription.
+        pub fn reinstate_subscription(&mut self, subscription_id: NonFungibleLocalId) {
+            let subscription: OracleSubscription = self
+                .subscription_manager
+                .get_non_fungible_data(&subscription_id);
+            assert!(
+                subscription.suspended,
+                "{}",
+                OracleError::SubscriptionNotSuspended
+            );
+
+            self.subscription_manager
+                .update_non_fungible_data(&subscription_id, "suspended", false);
+            self.subscription_manager.update_non_fungible_data(
+                &subscription_id,
+                "suspension_reason",
+                "".to_string(),
+            );
+
+            Runtime::emit_event(OracleSubscriptionReinstated {
+                nft_id: subscription_id,
+            });
+        }
+
+        /// Sets the image of subscriptions minted from now on.
+        pub fn set_subscription_image_url(&mut self, image_url: String) {
+            assert!(
+                !image_url.is_empty(),
+                "{}",
+                OracleError::MissingSubscriptionImageUrl
+            );
+
+            self.subscription_image_url = image_url;
+        }
+
+        /// Configures trial subscriptions.
+        ///
+        /// # Arguments
+        ///
+        /// * `days`: duration of a trial, 0 disables trials.
+        /// * `calls`: number of API calls included in a trial, enforced through the call count
+        ///   synced by the oracle backend.
+        pub fn set_trial_config(&mut self, days: u64, calls: u64) {
+            self.trial_days = days;
+            self.trial_calls = calls;
+        }
+
+        /// Configures the grace period after a subscription expired.
+        ///
+        /// # Arguments
+        ///
+        /// * `period`: seconds after expiration during which calls are still accepted.
+        /// * `calls`: maximum number of calls accepted during the grace period.
+        pub fn set_grace_period(&mut self, period: u64, calls: u64) {
+            self.grace_period = period;
+            self.grace_period_calls = calls;
+        }
+
+        /// Configures signer staking.
+        ///
+        /// # Arguments
+        ///
+        /// * `min_stake`: minimum XRD stake to join the signer set, `None` closes staking.
+        /// * `unbonding_delay`: seconds between unstaking and being able to withdraw the stake.
+        pub fn set_signer_staking(&mut self, min_stake: Option<Decimal>, unbonding_delay: u64) {
+            self.min_signer_stake = min_stake;
+            self.unbonding_delay = unbonding_delay;
+        }
+
+        /// Slashes the stake of a signer which signed two different prices for the same market and
+        /// nonce, and removes it from the signer set.
+        ///
+        /// The signer is removed even if it is the last one, the admin then needs to add a new one.
+        ///
+        /// # Arguments
+        ///
+        /// * `key_type`, `public_key`: the signer which signed both messages.
+        /// * `message_a`, `signature_a`: the first signed price message.
+        /// * `message_b`, `signature_b`: the conflicting signed price message.
+        ///
+        /// # Returns
+        /// The slashed stake.
+        pub fn slash_signer(
+            &mut self,
+            key_type: OracleKeyType,
+            public_key: String,
+            message_a: String,
+            signature_a: String,
+            message_b: String,
+            signature_b: String,
+        ) -> FungibleBucket {
+            let signer = OraclePublicKey::parse(key_type, public_key.as_str());
+            let (price_message, other_price_message) = self.check_equivocation(
+                &signer,
+                &message_a,
+                &signature_a,
+                &message_b,
+                &signature_b,
+            );
+            // The offense may already have been reported
+            self.record_equivocation(&signer, &price_message, &other_price_message);
+
+            self.slash_stake(signer, price_message)
+        }
+
+        /// Slashes the stake of a signer which signed a price the admin proved wrong, e.g. against
+        /// the markets at its creation time, and removes it from the signer set.
+        ///
+        /// # Arguments
+        ///
+        /// * `key_type`, `public_key`: the signer of the wrong price.
+        /// * `message`, `signature`: the signed price message.
+        ///
+        /// # Returns
+        /// The slashed stake.
+        pub fn slash_signer_for_wrong_price(
+            &mut self,
+            key_type: OracleKeyType,
+            public_key: String,
+            message: String,
+            signature: String,
+        ) -> FungibleBucket {
+            let signer = OraclePublicKey::parse(key_type, public_key.as_str());
+            check_signature(&message, &signature, &signer);
+
+            let price_message =
+                PriceMessage::from_str(&message).unwrap_or_else(|err| panic!("{}", err));
+
+            self.slash_stake(signer, price_message)
+        }
+
+        /// Sets whether signers reported through `report_equivocation` are removed from the signer
+        /// set right away.
+        pub fn set_disable_equivocating_signers(&mut self, disable: bool) {
+            self.disable_equivocating_signers = disable;
+        }
+
+        /// Configures pay per call subscriptions.
+        ///
+        /// # Arguments
+        ///
+        /// * `price_per_call`: XRD paid by every call, `None` makes pay per call subscriptions
+        ///   unavailable, including existing ones.
+        /// * `low_credit_threshold`: credit under which an `OracleSubscriptionLowCredit` event is emitted.
+        pub fn set_metered_billing(
+            &mut self,
+            price_per_call: Option<Decimal>,
+            low_credit_threshold: Decimal,
+        ) {
+            self.price_per_call = price_per_call;
+            self.low_credit_threshold = low_credit_threshold;
+        }
+
+        /// Configures the price deviation guard.
+        ///
+        /// # Arguments
+        ///
+        /// * `max_deviation`: maximum deviation in percent from the last accepted price, `None` disables the guard.
+        /// * `window`: seconds after the last accepted price during which the deviation is limited, later messages
+        ///   are accepted and become the new reference. Messages older than the window before the last accepted
+        ///   price are rejected, unless they are co-signed by the emergency key.
+        pub fn set_price_deviation_guard(&mut self, max_deviation: Option<Decimal>, window: u64) {
+            if let Some(max_deviation) = max_deviation {
+                assert!(
+                    max_deviation >= Decimal::ZERO,
+                    "{}",
+                    OracleError::NegativeMaxDeviation
+                );
+            }
+
+            self.max_price_deviation = max_deviation;
+            self.price_deviation_window = window;
+        }
+
+        /// Sets the key signing migration receipts and how long after their export they can be
+        /// imported.
+        ///
+        /// Staked keys cannot be the migration key, it has to be held by the admin.
+        ///
+        /// # Arguments
+        ///
+        /// * `migration_public_key`: signature scheme and hex encoded key, `None` closes imports.
+        /// * `max_receipt_age`: seconds after its export during which a receipt can be imported.
+        pub fn set_migration_pub_key(
+            &mut self,
+            migration_public_key: Option<(OracleKeyType, String)>,
+            max_receipt_age: u64,
+        ) {
+            let migration_pub_key = migration_public_key
+                .map(|(key_type, key)| OraclePublicKey::parse(key_type, key.as_str()));
+
+            if let Some(key) = &migration_pub_key {
+                assert!(
+                    !self.staked_signers.contains_key(key),
+                    "{}",
+                    OracleError::StakedMigrationKey
+                );
+            }
+
+            self.migration_pub_key = migration_pub_key;
+            self.max_migration_receipt_age = max_receipt_age;
+        }
+
+        /// Sets the key allowed to co-sign messages that bypass the price deviation guard.
+        pub fn set_emergency_pub_key(
+            &mut self,
+            emergency_public_key: Option<(OracleKeyType, String)>,
+        ) {
+            self.emergency_pub_key = emergency_public_key
+                .map(|(key_type, key)| OraclePublicKey::parse(key_type, key.as_str()));
+        }
+
+        /// Adds a key to the signer set.
+        ///
+        /// # Arguments
+        ///
+        /// * `key_type`: signature scheme of the key.
+        /// * `public_key`: hex encoded public key.
+        pub fn add_signer(&mut self, key_type: OracleKeyType, public_key: String) {
+            let signer = OraclePublicKey::parse(key_type, public_key.as_str());
+            assert!(
+                !self.signers.contains(&signer),
+                "{}",
+                OracleError::SignerAlreadyExists
+            );
+
+            self.signers.push(signer);
+        }
+
+        /// Removes a key from the signer set.
+        pub fn remove_signer(&mut self, key_type: OracleKeyType, public_key: String) {
+            let signer = OraclePublicKey::parse(key_type, public_key.as_str());
+            let index = self
+                .signers
+                .iter()
+                .position(|key| *key == signer)
+                .unwrap_or_else(|| panic!("{}", OracleError::SignerNotFound));
+
+            assert!(
+                self.signers.len() > 1,
+                "{}",
+                OracleError::LastSigner
+            );
+
+            self.signers.remove(index);
+        }
+
+        /// Collects subscription fees.
+        ///
+        /// # Returns
+        /// All paid subscription fees.
+        pub fn collect_subscription_fees(&mut self) -> FungibleBucket {
+            self.subscription_fees_vault.take_all()
+        }
+
+        fn check_payment(&mut self, months: u64, mut payment: FungibleBucket) -> FungibleBucket {
+            assert!(months > 0, "{}", OracleError::ZeroMonths);
+            assert!(
+                payment.resource_address() == XRD,
+                "{}",
+                OracleError::PaymentNotInXrd
+            );
+
+            let expected_payment = months * self.monthly_subscription_fee;
+            assert!(
+                expected_payment <= payment.amount(),
+                "{}",
+                OracleError::InsufficientPayment {
+                    months,
+                    expected: expected_payment,
+                    supplied: payment.amount(),
+                }
             );
 
             self.subscription_fees_vault
@@ -354,5 +2206,578 @@ mod morpher_oracle {
 
             payment
         }
+
+        /// Extends a subscription by `months` months from its expiration, or from now if it expired.
+        fn extend_subscription(&mut self, subscription_id: &NonFungibleLocalId, months: u64) {
+            let subscription_data: OracleSubscription = self
+                .subscription_manager
+                .get_non_fungible_data(subscription_id);
+
+            let new_subscription_end = max(get_time(), subscription_data.expiration_time)
+                + SECONDS_IN_A_MONTH * (months);
+
+            self.subscription_manager.update_non_fungible_data(
+                subscription_id,
+                "expiration_time",
+                new_subscription_end,
+            );
+
+            self.subscription_manager.update_non_fungible_data(
+                subscription_id,
+                "max_nonce",
+                subscription_data.max_nonce + (API_CALLS_PER_MONTH * months),
+            );
+
+            self.subscription_manager.update_non_fungible_data(
+                subscription_id,
+                "grace_calls",
+                0u64,
+            );
+
+            // Renewed trials and complimentary subscriptions become paid monthly subscriptions
+            self.subscription_manager
+                .update_non_fungible_data(subscription_id, "trial", false);
+            self.subscription_manager.update_non_fungible_data(
+                subscription_id,
+                "complimentary",
+                false,
+            );
+            self.subscription_manager.update_non_fungible_data(
+                subscription_id,
+                "tier",
+                Self::display_tier(false, false, BillingMode::Monthly),
+            );
+
+            self.subscription_manager.update_non_fungible_data(
+                subscription_id,
+                "expiry_date",
+                Self::display_expiry_date(new_subscription_end),
+            );
+
+            Runtime::emit_event(OracleSubscriptionUpdate {
+                new_expiration_time: new_subscription_end,
+                nft_id: subscription_id.clone(),
+                complimentary: false,
+            });
+        }
+
+        /// Pays a month of subscription out of its credit, returns `false` if the credit is too low.
+        fn renew_from_credit(&mut self, subscription_id: &NonFungibleLocalId) -> bool {
+            let fee = self.monthly_subscription_fee;
+
+            let payment = match self.subscription_credits.get_mut(subscription_id) {
+                Some(mut credit) if credit.amount() >= fee => credit.take(fee),
+                _ => return false,
+            };
+            self.subscription_fees_vault.put(payment);
+
+            self.extend_subscription(subscription_id, 1);
+
+            true
+        }
+
+        /// Pays a call of a pay per call subscription out of its credit.
+        fn charge_call(&mut self, subscription_id: &NonFungibleLocalId) {
+            let price_per_call = self
+                .price_per_call
+                .unwrap_or_else(|| panic!("{}", OracleError::MeteredBillingUnavailable));
+
+            let (payment, credit) = {
+                let mut credit = self
+                    .subscription_credits
+                    .get_mut(subscription_id)
+                    .filter(|credit| credit.amount() >= price_per_call)
+                    .unwrap_or_else(|| panic!("{}", OracleError::InsufficientCredit));
+
+                (credit.take(price_per_call), credit.amount())
+            };
+            self.subscription_fees_vault.put(payment);
+
+            if credit < self.low_credit_threshold
+                && credit + price_per_call >= self.low_credit_threshold
+            {
+                Runtime::emit_event(OracleSubscriptionLowCredit {
+                    nft_id: subscription_id.clone(),
+                    credit,
+                });
+            }
+        }
+
+        fn mint_subscription(
+            &mut self,
+            expiration_time: u64,
+            max_nonce: u64,
+            trial: bool,
+            complimentary: bool,
+            billing_mode: BillingMode,
+        ) -> NonFungibleBucket {
+            let subscription_id = NonFungibleLocalId::integer(self.next_subscription_id);
+            self.next_subscription_id += 1;
+
+            let subscription_data = OracleSubscription {
+                name: format!("Morpher subscription {}", subscription_id),
+                key_image_url: Url::of(self.subscription_image_url.clone()),
+                tier: Self::display_tier(trial, complimentary, billing_mode),
+                expiry_date: Self::display_expiry_date(expiration_time),
+                expiration_time,
+                cur_nonce: 0,
+                max_nonce,
+                authorized_pub_key: "".to_string(),
+                grace_calls: 0,
+                trial,
+                complimentary,
+                billing_mode,
+                suspended: false,
+                suspension_reason: "".to_string(),
+                rate_limit: None,
+            };
+
+            let subscription = self
+                .subscription_manager
+                .mint_non_fungible(&subscription_id, subscription_data);
+
+            Runtime::emit_event(OracleSubscriptionUpdate {
+                new_expiration_time: expiration_time,
+                nft_id: subscription.non_fungible_local_id(),
+                complimentary,
+            });
+
+            subscription
+        }
+
+        fn display_tier(trial: bool, complimentary: bool, billing_mode: BillingMode) -> String {
+            match (trial, complimentary, billing_mode) {
+                (true, _, _) => "Trial",
+                (false, true, _) => "Complimentary",
+                (false, false, BillingMode::Monthly) => "Monthly",
+                (false, false, BillingMode::PayPerCall) => "Pay per call",
+            }
+            .to_string()
+        }
+
+        fn display_expiry_date(expiration_time: u64) -> String {
+            if expiration_time == u64::MAX {
+                "Never".to_string()
+            } else {
+                format_utc_date(expiration_time)
+            }
+        }
+
+        /// Checks that the subscription can make a call and counts the call against its quota.
+        ///
+        /// Expired subscriptions keep working during the grace period, against a reduced quota.
+        fn use_subscription(&mut self, subscription_proof: NonFungibleProof) {
+            // Delegates call against their own quota, carved out of the subscription
+            if subscription_proof.resource_address() == self.delegate_manager.address() {
+                self.use_delegate(subscription_proof);
+                return;
+            }
+
+            let checked_proof = subscription_proof.check(self.subscription_manager.address());
+            let subscription_id = checked_proof.non_fungible_local_id();
+            let mut subscription_data: OracleSubscription = self
+                .subscription_manager
+                .get_non_fungible_data(&subscription_id);
+
+            assert!(
+                !subscription_data.suspended,
+                "{}",
+                OracleError::SubscriptionSuspended
+            );
+
+            self.count_rate_limited_call(&subscription_id, &subscription_data);
+
+            if subscription_data.billing_mode == BillingMode::PayPerCall {
+                self.charge_call(&subscription_id);
+            }
+
+            // Expired subscriptions with enough credit are renewed before the grace period applies
+            if get_time() > subscription_data.expiration_time
+                && self.renew_from_credit(&subscription_id)
+            {
+                subscription_data = self
+                    .subscription_manager
+                    .get_non_fungible_data(&subscription_id);
+            }
+
+            if get_time() <= subscription_data.expiration_time {
+                // The calls are counted by the oracle backend, which signed the price for this
+                // call, and synced with set_subscription_cur_nonce
+                assert!(
+                    subscription_data.cur_nonce < subscription_data.max_nonce,
+                    "{}",
+                    OracleError::NoCallsLeft
+                );
+            } else {
+                assert!(
+                    get_time() <= subscription_data.expiration_time + self.grace_period
+                        && subscription_data.grace_calls < self.grace_period_calls,
+                    "{}",
+                    OracleError::SubscriptionExpired
+                );
+
+                self.subscription_manager.update_non_fungible_data(
+                    &subscription_id,
+                    "grace_calls",
+                    subscription_data.grace_calls + 1,
+                );
+
+                Runtime::emit_event(OracleSubscriptionGracePeriodCall {
+                    nft_id: subscription_id,
+                    expiration_time: subscription_data.expiration_time,
+                    grace_calls_left: self.grace_period_calls - subscription_data.grace_calls - 1,
+                });
+            }
+        }
+
+        /// Checks that the delegate can make a call and counts the call against its quota.
+        fn use_delegate(&mut self, delegate_proof: NonFungibleProof) {
+            let checked_proof = delegate_proof.check(self.delegate_manager.address());
+            let delegate_id = checked_proof.non_fungible_local_id();
+            let delegate: SubscriptionDelegate =
+                self.delegate_manager.get_non_fungible_data(&delegate_id);
+
+            assert!(!delegate.revoked, "{}", OracleError::DelegateRevoked);
+
+            // Exporting burns the subscription, which stops its delegates
+            assert!(
+                self.subscription_manager
+                    .non_fungible_exists(&delegate.parent_id),
+                "{}",
+                OracleError::SubscriptionExported
+            );
+
+            // Delegates of a suspended subscription are suspended too and share its rate limit
+            let parent: OracleSubscription = self
+                .subscription_manager
+                .get_non_fungible_data(&delegate.parent_id);
+            assert!(!parent.suspended, "{}", OracleError::SubscriptionSuspended);
+
+            self.count_rate_limited_call(&delegate.parent_id, &parent);
+
+            assert!(
+                get_time() <= delegate.expiration_time,
+                "{}",
+                OracleError::SubscriptionExpired
+            );
+            assert!(
+                delegate.calls_used < delegate.max_calls,
+                "{}",
+                OracleError::NoCallsLeft
+            );
+
+            self.delegate_manager.update_non_fungible_data(
+                &delegate_id,
+                "calls_used",
+                delegate.calls_used + 1,
+            );
+        }
+
+        /// Counts a call against the rate limit of the subscription, if it has one.
+        fn count_rate_limited_call(
+            &self,
+            subscription_id: &NonFungibleLocalId,
+            subscription: &OracleSubscription,
+        ) {
+            if let Some(mut rate_limit) = subscription.rate_limit.clone() {
+                let now = get_time();
+                assert!(rate_limit.try_call(now), "{}", OracleError::RateLimited);
+
+                if rate_limit.is_exhausted(now) {
+                    Runtime::emit_event(OracleSubscriptionThrottled {
+                        nft_id: subscription_id.clone(),
+                        max_calls: rate_limit.max_calls,
+                        window: rate_limit.window,
+                    });
+                }
+
+                self.subscription_manager.update_non_fungible_data(
+                    subscription_id,
+                    "rate_limit",
+                    Some(rate_limit),
+                );
+            }
+        }
+
+        fn subscription_remaining_calls(
+            &self,
+            subscription_id: &NonFungibleLocalId,
+            subscription: &OracleSubscription,
+        ) -> u64 {
+            let now = get_time();
+
+            if subscription.billing_mode == BillingMode::PayPerCall {
+                let credit = self.get_subscription_credit(subscription_id.clone());
+
+                return match self.price_per_call {
+                    Some(price_per_call) if price_per_call.is_zero() => u64::MAX,
+                    Some(price_per_call) => credit
+                        .checked_div(price_per_call)
+                        .and_then(|calls| calls.checked_floor())
+                        .and_then(|calls| u64::try_from(calls.attos() / Decimal::ONE.attos()).ok())
+                        .unwrap_or(u64::MAX),
+                    None => 0,
+                };
+            }
+
+            if now <= subscription.expiration_time {
+                subscription.max_nonce.saturating_sub(subscription.cur_nonce)
+            } else if now <= subscription.expiration_time + self.grace_period {
+                self.grace_period_calls.saturating_sub(subscription.grace_calls)
+            } else {
+                0
+            }
+        }
+
+        /// Checks signature, validity window and nonce of a price message without recording anything.
+        fn validate_price_input(&self, message: &str, signature: &str) -> PriceMessage {
+
+            // Then check the message is correct
+            self.check_signer_signature(message, signature);
+
+            // If everything is fine, parse the message
+            let price_message =
+                PriceMessage::from_str(message).unwrap_or_else(|err| panic!("{}", err));
+
+            // Check that the message is inside the validity window signed by the oracle
+            check_validity_window(price_message.created_at, price_message.valid_until);
+
+            // Derived prices carry this nonce, a signed message must never pass as one of them
+            assert!(
+                price_message.nonce != DERIVED_NONCE,
+                "{}",
+                OracleError::ReservedNonce
+            );
+
+            // Check that the nonce has not been used
+            assert!(
+                !self.used_nonce.contains(&price_message.nonce),
+                "{}",
+                OracleError::NonceAlreadyUsed
+            );
+
+            price_message
+        }
+
+        fn accept_price_input(
+            &mut self,
+            message: String,
+            signature: String,
+            emergency_signature: Option<String>,
+        ) -> PriceMessage {
+            let price_message = self.validate_price_input(&message, &signature);
+
+            // Consume the nonce so the message cannot be replayed
+            self.used_nonce.insert(price_message.nonce);
+
+            // Large moves are only accepted when the emergency key co-signed the same message
+            match emergency_signature {
+                Some(emergency_signature) => {
+                    let emergency_pub_key = self
+                        .emergency_pub_key
+                        .as_ref()
+                        .unwrap_or_else(|| panic!("{}", OracleError::NoEmergencyKey));
+                    check_signature(&message, &emergency_signature, emergency_pub_key);
+                }
+                None => self.check_price_deviation(
+                    &price_message.market_id,
+                    price_message.price,
+                    price_message.created_at,
+                ),
+            }
+
+            self.record_price(
+                &price_message.market_id,
+                price_message.price,
+                price_message.created_at,
+            );
+
+            price_message
+        }
+
+        /// Checks that the message is signed by one of the signers.
+        fn check_signer_signature(&self, message: &str, signature: &str) {
+            assert!(
+                self.signers
+                    .iter()
+                    .any(|signer| verify_signature(message, signature, signer)),
+                "{}",
+                OracleError::SignatureVerificationFailed
+            );
+        }
+
+        /// Checks that two messages are conflicting prices signed by the given signer.
+        fn check_equivocation(
+            &self,
+            signer: &OraclePublicKey,
+            message_a: &str,
+            signature_a: &str,
+            message_b: &str,
+            signature_b: &str,
+        ) -> (PriceMessage, PriceMessage) {
+            // Unbonding signers are no longer in the signer set but can still be held accountable
+            assert!(
+                self.signers.contains(signer) || self.staked_signers.contains_key(signer),
+                "{}",
+                OracleError::SignerNotFound
+            );
+
+            check_signature(message_a, signature_a, signer);
+            check_signature(message_b, signature_b, signer);
+
+            let price_message_a =
+                PriceMessage::from_str(message_a).unwrap_or_else(|err| panic!("{}", err));
+            let price_message_b =
+                PriceMessage::from_str(message_b).unwrap_or_else(|err| panic!("{}", err));
+
+            assert!(
+                price_message_a.conflicts_with(&price_message_b),
+                "{}",
+                OracleError::NotAnEquivocation
+            );
+
+            (price_message_a, price_message_b)
+        }
+
+        /// Takes the whole stake of a signer for an offense in `price_message` and removes the key
+        /// from the signer set, even if it is the last one.
+        fn slash_stake(
+            &mut self,
+            signer: OraclePublicKey,
+            price_message: PriceMessage,
+        ) -> FungibleBucket {
+            let stake_id = self
+                .staked_signers
+                .remove(&signer)
+                .unwrap_or_else(|| panic!("{}", OracleError::SignerNotStaked));
+            let stake: SignerStake = self.stake_manager.get_non_fungible_data(&stake_id);
+
+            self.stake_manager
+                .update_non_fungible_data(&stake_id, "amount", Decimal::ZERO);
+            // The emptied stake can be withdrawn right away, which burns its NFT
+            if stake.unbonding_until.is_none() {
+                self.stake_manager.update_non_fungible_data(
+                    &stake_id,
+                    "unbonding_until",
+                    Some(get_time()),
+                );
+            }
+            self.signers.retain(|key| *key != signer);
+
+            Runtime::emit_event(SignerSlashed {
+                public_key: signer,
+                stake_id,
+                amount: stake.amount,
+                market_id: price_message.market_id,
+                nonce: price_message.nonce,
+            });
+
+            self.stake_vault.take(stake.amount)
+        }
+
+        /// Records conflicting prices of a signer, returns `false` if they were already recorded.
+        fn record_equivocation(
+            &mut self,
+            signer: &OraclePublicKey,
+            price_message_a: &PriceMessage,
+            price_message_b: &PriceMessage,
+        ) -> bool {
+            let report_key = (
+                signer.clone(),
+                price_message_a.market_id.clone(),
+                price_message_a.nonce,
+            );
+
+            if self.equivocation_reports.get(&report_key).is_some() {
+                return false;
+            }
+
+            self.equivocation_reports.insert(
+                report_key,
+                EquivocationReport {
+                    price_a: price_message_a.price,
+                    price_b: price_message_b.price,
+                    reported_at: get_time(),
+                },
+            );
+
+            let offenses = self
+                .equivocating_signers
+                .get(signer)
+                .map_or(0, |offenses| *offenses);
+            self.equivocating_signers.insert(signer.clone(), offenses + 1);
+
+            true
+        }
+
+        fn check_price_deviation(&self, market_id: &String, price: Decimal, created_at: u64) {
+            let max_deviation = match self.max_price_deviation {
+                Some(max_deviation) => max_deviation,
+                None => return,
+            };
+
+            if let Some(last_price) = self.last_prices.get(market_id) {
+                // Once the window has passed the reference is too old to compare with, so that
+                // quiet markets keep updating. The price becomes the new reference.
+                let window_end = last_price
+                    .created_at
+                    .saturating_add(self.price_deviation_window);
+                if created_at > window_end {
+                    return;
+                }
+
+                // Messages older than the window cannot be compared either, but they would not
+                // replace the reference
+                assert!(
+                    created_at.saturating_add(self.price_deviation_window) >= last_price.created_at,
+                    "{}",
+                    OracleError::PriceOutsideDeviationWindow
+                );
+
+                if last_price.price.is_zero() {
+                    return;
+                }
+
+                let deviation = price
+                    .checked_sub(last_price.price)
+                    .and_then(|difference| difference.checked_abs())
+                    .and_then(|difference| difference.checked_div(last_price.price.checked_abs()?))
+                    .and_then(|ratio| ratio.checked_mul(dec!(100)))
+                    .unwrap_or_else(|| panic!("{}", OracleError::PriceDeviationOverflow));
+
+                assert!(
+                    deviation <= max_deviation,
+                    "{}",
+                    OracleError::PriceDeviationTooLarge {
+                        deviation,
+                        max_deviation,
+                    }
+                );
+            }
+        }
+
+        /// Records an accepted price as reference for the deviation guard and in the TWAP.
+        fn record_price(&mut self, market_id: &String, price: Decimal, created_at: u64) {
+            // Only newer messages replace the reference price of a market
+            let is_newer = self
+                .last_prices
+                .get(market_id)
+                .is_none_or(|last_price| last_price.created_at <= created_at);
+
+            if is_newer {
+                self.last_prices
+                    .insert(market_id.clone(), LastPrice { price, created_at });
+            }
+
+            let mut accumulator = self
+                .twap_accumulators
+                .get(market_id)
+                .map(|accumulator| accumulator.clone())
+                .unwrap_or_default();
+
+            if accumulator.record(price, created_at) {
+                self.twap_accumulators.insert(market_id.clone(), accumulator);
+            }
+        }
     }
 }
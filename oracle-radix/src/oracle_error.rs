@@ -0,0 +1,435 @@
+use std::fmt;
+
+use scrypto::prelude::*;
+
+/// Errors raised by the oracle.
+///
+/// Failure messages start with the stable code of the error, e.g.
+/// `[ORACLE-200] Verification of signature failed!`, so off-chain tooling can map failed
+/// transactions to their cause. Codes are never reused or renumbered.
+#[derive(ScryptoSbor, Clone, Debug, PartialEq, Eq)]
+pub enum OracleError {
+    // Message parsing
+    InvalidMessageFormat,
+    InvalidMarketId,
+    InvalidPrice,
+    InvalidNonce,
+    InvalidCreationDate,
+    InvalidBid,
+    InvalidAsk,
+    InvalidConfidence,
+    InvalidValidUntil,
+    BidAboveAsk,
+    ExpiresBeforeCreation,
+    InvalidRequestPublicKey,
+    InvalidAddress,
+    InvalidBasketEntry,
+    DuplicateBasketMarket,
+    InvalidSubscriptionId,
+    InvalidRemainingTime,
+    InvalidRemainingCalls,
+    PriceOutsideBidAsk,
+    NegativeConfidence,
+    NonPositiveBasketPrice,
+
+    // Signatures and keys
+    SignatureVerificationFailed,
+    InvalidPublicKey,
+    SignerAlreadyExists,
+    SignerNotFound,
+    LastSigner,
+    NoEmergencyKey,
+    NoMigrationKey,
+    StakedMigrationKey,
+
+    // Price checks
+    PriceMessageExpired,
+    PriceMessageFromTheFuture,
+    NonceAlreadyUsed,
+    PriceDeviationTooLarge {
+        deviation: Decimal,
+        max_deviation: Decimal,
+    },
+    ConfidenceTooWide {
+        confidence: Decimal,
+        max_confidence: Decimal,
+    },
+    NotEnoughTwapObservations,
+    NonPositiveCrossRatePrice,
+    NegativeMaxDeviation,
+    PriceRequestNotFound,
+    MarketMismatch,
+    PriceDeviationOverflow,
+    PriceOutsideDeviationWindow,
+    CrossRateOverflow,
+    ReservedNonce,
+
+    // Subscriptions
+    ZeroMonths,
+    PaymentNotInXrd,
+    InsufficientPayment {
+        months: u64,
+        expected: Decimal,
+        supplied: Decimal,
+    },
+    SubscriptionExpired,
+    NoCallsLeft,
+    TrialsNotAvailable,
+    TrialAlreadyClaimed,
+    NonceNotIncreasing,
+    RateLimited,
+    InvalidRateLimit,
+    NoSubscriptionCredit,
+    MeteredBillingUnavailable,
+    InsufficientCredit,
+    PayPerCallSubscription,
+    DelegationNotSupported,
+    DelegateOutlivesSubscription,
+    DelegateNotOwned,
+    DelegateRevoked,
+    SubscriptionAlreadyImported,
+    SubscriptionSuspended,
+    SubscriptionNotSuspended,
+    MigrationTargetMismatch,
+    SubscriptionExported,
+    MissingSubscriptionImageUrl,
+    SubscriptionDurationOverflow,
+    CreditWithdrawalTooLarge,
+    MigrationReceiptTooOld,
+    ActiveDelegates,
+    MigrationRecipientMismatch,
+
+    // Signer staking
+    StakingClosed,
+    InsufficientStake {
+        minimum: Decimal,
+        supplied: Decimal,
+    },
+    InvalidStake,
+    AlreadyUnbonding,
+    StakeNotUnbonding,
+    UnbondingNotFinished,
+    NotAnEquivocation,
+    SignerNotStaked,
+    EquivocationAlreadyReported,
+    EquivocatingSigner,
+}
+
+impl OracleError {
+    /// Stable numeric code of the error, grouped by hundreds per category.
+    pub fn code(&self) -> u16 {
+        match self {
+            OracleError::InvalidMessageFormat => 100,
+            OracleError::InvalidMarketId => 101,
+            OracleError::InvalidPrice => 102,
+            OracleError::InvalidNonce => 103,
+            OracleError::InvalidCreationDate => 104,
+            OracleError::InvalidBid => 105,
+            OracleError::InvalidAsk => 106,
+            OracleError::InvalidConfidence => 107,
+            OracleError::InvalidValidUntil => 108,
+            OracleError::BidAboveAsk => 109,
+            OracleError::ExpiresBeforeCreation => 110,
+            OracleError::InvalidRequestPublicKey => 111,
+            OracleError::InvalidAddress => 112,
+            OracleError::InvalidBasketEntry => 113,
+            OracleError::DuplicateBasketMarket => 114,
+            OracleError::InvalidSubscriptionId => 115,
+            OracleError::InvalidRemainingTime => 116,
+            OracleError::InvalidRemainingCalls => 117,
+            OracleError::PriceOutsideBidAsk => 118,
+            OracleError::NegativeConfidence => 119,
+            OracleError::NonPositiveBasketPrice => 120,
+
+            OracleError::SignatureVerificationFailed => 200,
+            OracleError::InvalidPublicKey => 201,
+            OracleError::SignerAlreadyExists => 202,
+            OracleError::SignerNotFound => 203,
+            OracleError::LastSigner => 204,
+            OracleError::NoEmergencyKey => 205,
+            OracleError::NoMigrationKey => 206,
+            OracleError::StakedMigrationKey => 207,
+
+            OracleError::PriceMessageExpired => 300,
+            OracleError::PriceMessageFromTheFuture => 301,
+            OracleError::NonceAlreadyUsed => 302,
+            OracleError::PriceDeviationTooLarge { .. } => 303,
+            OracleError::ConfidenceTooWide { .. } => 304,
+            OracleError::NotEnoughTwapObservations => 305,
+            OracleError::NonPositiveCrossRatePrice => 306,
+            OracleError::NegativeMaxDeviation => 307,
+            OracleError::PriceRequestNotFound => 308,
+            OracleError::MarketMismatch => 309,
+            OracleError::PriceDeviationOverflow => 310,
+            OracleError::PriceOutsideDeviationWindow => 311,
+            OracleError::CrossRateOverflow => 312,
+            OracleError::ReservedNonce => 313,
+
+            OracleError::ZeroMonths => 400,
+            OracleError::PaymentNotInXrd => 401,
+            OracleError::InsufficientPayment { .. } => 402,
+            OracleError::SubscriptionExpired => 403,
+            OracleError::NoCallsLeft => 404,
+            OracleError::TrialsNotAvailable => 405,
+            OracleError::TrialAlreadyClaimed => 406,
+            OracleError::NonceNotIncreasing => 407,
+            OracleError::RateLimited => 408,
+            OracleError::InvalidRateLimit => 409,
+            OracleError::NoSubscriptionCredit => 410,
+            OracleError::MeteredBillingUnavailable => 411,
+            OracleError::InsufficientCredit => 412,
+            OracleError::PayPerCallSubscription => 413,
+            OracleError::DelegationNotSupported => 414,
+            OracleError::DelegateOutlivesSubscription => 415,
+            OracleError::DelegateNotOwned => 416,
+            OracleError::DelegateRevoked => 417,
+            OracleError::SubscriptionAlreadyImported => 419,
+            OracleError::SubscriptionSuspended => 420,
+            OracleError::SubscriptionNotSuspended => 421,
+            OracleError::MigrationTargetMismatch => 422,
+            OracleError::SubscriptionExported => 423,
+            OracleError::MissingSubscriptionImageUrl => 424,
+            OracleError::SubscriptionDurationOverflow => 425,
+            OracleError::CreditWithdrawalTooLarge => 426,
+            OracleError::MigrationReceiptTooOld => 427,
+            OracleError::ActiveDelegates => 428,
+            OracleError::MigrationRecipientMismatch => 429,
+
+            OracleError::StakingClosed => 500,
+            OracleError::InsufficientStake { .. } => 501,
+            OracleError::InvalidStake => 502,
+            OracleError::AlreadyUnbonding => 503,
+            OracleError::StakeNotUnbonding => 504,
+            OracleError::UnbondingNotFinished => 505,
+            OracleError::NotAnEquivocation => 506,
+            OracleError::SignerNotStaked => 507,
+            OracleError::EquivocationAlreadyReported => 508,
+            OracleError::EquivocatingSigner => 509,
+        }
+    }
+}
+
+impl fmt::Display for OracleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[ORACLE-{}] ", self.code())?;
+
+        match self {
+            OracleError::InvalidMessageFormat => write!(f, "Failed to parse input message"),
+            OracleError::InvalidMarketId => write!(f, "Could not parse the market id"),
+            OracleError::InvalidPrice => write!(f, "Could not parse the price"),
+            OracleError::InvalidNonce => write!(f, "Could not parse the nonce"),
+            OracleError::InvalidCreationDate => write!(f, "Could not parse the creation date"),
+            OracleError::InvalidBid => write!(f, "Could not parse the bid"),
+            OracleError::InvalidAsk => write!(f, "Could not parse the ask"),
+            OracleError::InvalidConfidence => write!(f, "Could not parse the confidence"),
+            OracleError::InvalidValidUntil => write!(f, "Could not parse the validity end"),
+            OracleError::BidAboveAsk => write!(f, "The bid cannot be above the ask"),
+            OracleError::ExpiresBeforeCreation => {
+                write!(f, "The message cannot expire before it was created")
+            }
+            OracleError::InvalidRequestPublicKey => write!(f, "Could not parse the bls key"),
+            OracleError::InvalidAddress => write!(f, "Could not parse the address"),
+            OracleError::InvalidBasketEntry => write!(f, "Could not parse a basket entry"),
+            OracleError::DuplicateBasketMarket => {
+                write!(f, "A market appears twice in the basket")
+            }
+            OracleError::InvalidSubscriptionId => write!(f, "Could not parse the subscription id"),
+            OracleError::InvalidRemainingTime => write!(f, "Could not parse the remaining time"),
+            OracleError::InvalidRemainingCalls => {
+                write!(f, "Could not parse the remaining calls")
+            }
+            OracleError::PriceOutsideBidAsk => {
+                write!(f, "The price has to be between the bid and the ask")
+            }
+            OracleError::NegativeConfidence => write!(f, "The confidence cannot be negative"),
+            OracleError::NonPositiveBasketPrice => {
+                write!(f, "The prices of a basket have to be positive")
+            }
+
+            OracleError::SignatureVerificationFailed => {
+                write!(f, "Verification of signature failed!")
+            }
+            OracleError::InvalidPublicKey => write!(f, "The given public key is not valid"),
+            OracleError::SignerAlreadyExists => write!(f, "This key is already a signer"),
+            OracleError::SignerNotFound => write!(f, "This key is not a signer"),
+            OracleError::LastSigner => write!(f, "The oracle needs at least one signer!"),
+            OracleError::NoEmergencyKey => write!(f, "No emergency key has been configured"),
+            OracleError::NoMigrationKey => write!(f, "No migration key has been configured"),
+            OracleError::StakedMigrationKey => {
+                write!(f, "A staked key cannot sign migration receipts")
+            }
+
+            OracleError::PriceMessageExpired => write!(f, "This price message has expired!"),
+            OracleError::PriceMessageFromTheFuture => {
+                write!(f, "This price message was created in the future!")
+            }
+            OracleError::NonceAlreadyUsed => write!(f, "This nonce has already been used"),
+            OracleError::PriceDeviationTooLarge {
+                deviation,
+                max_deviation,
+            } => write!(
+                f,
+                "The price deviates {}% from the last accepted price (max {}%)",
+                deviation, max_deviation
+            ),
+            OracleError::ConfidenceTooWide {
+                confidence,
+                max_confidence,
+            } => write!(
+                f,
+                "The price confidence {} is wider than the maximum of {}",
+                confidence, max_confidence
+            ),
+            OracleError::NotEnoughTwapObservations => write!(
+                f,
+                "Not enough price observations for the requested TWAP window"
+            ),
+            OracleError::NonPositiveCrossRatePrice => {
+                write!(f, "Cannot derive a cross rate from non positive prices")
+            }
+            OracleError::NegativeMaxDeviation => {
+                write!(f, "The maximum price deviation cannot be negative!")
+            }
+            OracleError::PriceRequestNotFound => write!(f, "This price request does not exist"),
+            OracleError::MarketMismatch => {
+                write!(f, "The price message is not for the requested market")
+            }
+            OracleError::PriceDeviationOverflow => {
+                write!(f, "Could not compute the deviation from the last accepted price")
+            }
+            OracleError::PriceOutsideDeviationWindow => write!(
+                f,
+                "The price is too old to compare with the last accepted price, it needs the emergency key"
+            ),
+            OracleError::CrossRateOverflow => write!(f, "The cross rate is out of range"),
+            OracleError::ReservedNonce => write!(f, "This nonce is reserved for derived prices"),
+
+            OracleError::ZeroMonths => write!(f, "Cannot add 0 months to the subscription!"),
+            OracleError::PaymentNotInXrd => write!(f, "The payment should be made in XRD tokens!"),
+            OracleError::InsufficientPayment {
+                months,
+                expected,
+                supplied,
+            } => write!(
+                f,
+                "For {} months, the expected payment is {} XRD (only supplied {})",
+                months, expected, supplied
+            ),
+            OracleError::SubscriptionExpired => write!(f, "Subscription has expired!"),
+            OracleError::NoCallsLeft => write!(f, "No API calls left in this subscription!"),
+            OracleError::TrialsNotAvailable => write!(f, "Trial subscriptions are not available"),
+            OracleError::TrialAlreadyClaimed => {
+                write!(f, "This account already claimed a trial subscription")
+            }
+            OracleError::NonceNotIncreasing => {
+                write!(f, "The new nonce must be bigger than the previous nonce")
+            }
+            OracleError::RateLimited => {
+                write!(f, "The rate limit of this subscription has been reached")
+            }
+            OracleError::InvalidRateLimit => {
+                write!(f, "A rate limit needs at least one call and a window of at least 60 seconds")
+            }
+            OracleError::NoSubscriptionCredit => {
+                write!(f, "This subscription has never been credited")
+            }
+            OracleError::MeteredBillingUnavailable => {
+                write!(f, "Pay per call subscriptions are not available")
+            }
+            OracleError::InsufficientCredit => {
+                write!(f, "Not enough credit left to pay for this call")
+            }
+            OracleError::PayPerCallSubscription => {
+                write!(f, "Pay per call subscriptions cannot be renewed by the month")
+            }
+            OracleError::DelegationNotSupported => {
+                write!(f, "Pay per call subscriptions cannot mint delegates")
+            }
+            OracleError::DelegateOutlivesSubscription => {
+                write!(f, "A delegate cannot expire after its subscription")
+            }
+            OracleError::DelegateNotOwned => {
+                write!(f, "This delegate belongs to another subscription")
+            }
+            OracleError::DelegateRevoked => write!(f, "This delegate has been revoked"),
+            OracleError::SubscriptionAlreadyImported => {
+                write!(f, "This subscription has already been imported")
+            }
+            OracleError::SubscriptionSuspended => write!(f, "This subscription is suspended"),
+            OracleError::SubscriptionNotSuspended => {
+                write!(f, "This subscription is not suspended")
+            }
+            OracleError::MigrationTargetMismatch => {
+                write!(f, "This receipt was exported for another oracle deployment")
+            }
+            OracleError::SubscriptionExported => {
+                write!(f, "This subscription has been exported to another oracle deployment")
+            }
+            OracleError::MissingSubscriptionImageUrl => {
+                write!(f, "Subscriptions need an image URL")
+            }
+            OracleError::SubscriptionDurationOverflow => {
+                write!(f, "The subscription would expire after the end of time")
+            }
+            OracleError::CreditWithdrawalTooLarge => {
+                write!(f, "Cannot withdraw more than the credit of the subscription")
+            }
+            OracleError::MigrationReceiptTooOld => write!(f, "This migration receipt is too old"),
+            OracleError::ActiveDelegates => {
+                write!(f, "Revoke the delegates of this subscription before exporting it")
+            }
+            OracleError::MigrationRecipientMismatch => {
+                write!(f, "This migration receipt is for another recipient")
+            }
+
+            OracleError::StakingClosed => write!(f, "Signer staking is closed"),
+            OracleError::InsufficientStake { minimum, supplied } => write!(
+                f,
+                "The minimum signer stake is {} XRD (only supplied {})",
+                minimum, supplied
+            ),
+            OracleError::InvalidStake => write!(f, "This is not a signer stake"),
+            OracleError::AlreadyUnbonding => write!(f, "This stake is already unbonding"),
+            OracleError::StakeNotUnbonding => {
+                write!(f, "The stake needs to be unbonded before it can be withdrawn")
+            }
+            OracleError::UnbondingNotFinished => write!(f, "The stake is still unbonding"),
+            OracleError::NotAnEquivocation => write!(
+                f,
+                "The messages are not different prices for the same market and nonce"
+            ),
+            OracleError::SignerNotStaked => write!(f, "This signer has no stake"),
+            OracleError::EquivocationAlreadyReported => {
+                write!(f, "These conflicting prices have already been reported")
+            }
+            OracleError::EquivocatingSigner => {
+                write!(f, "This key has signed conflicting prices")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod oracle_error_tests {
+    use scrypto::prelude::*;
+
+    use crate::oracle_error::OracleError;
+
+    #[test]
+    pub fn test_display_starts_with_code() {
+        assert_eq!(
+            OracleError::SignatureVerificationFailed.to_string(),
+            "[ORACLE-200] Verification of signature failed!"
+        );
+
+        assert_eq!(
+            OracleError::InsufficientPayment {
+                months: 6,
+                expected: dec!(180),
+                supplied: dec!(170),
+            }
+            .to_string(),
+            "[ORACLE-402] For 6 months, the expected payment is 180 XRD (only supplied 170)"
+        );
+    }
+}
@@ -2,6 +2,8 @@ use std::str::FromStr;
 
 use scrypto::prelude::*;
 
+use crate::oracle_error::OracleError;
+
 #[derive(ScryptoSbor, Clone)]
 pub struct OracleRequestMessage {
     pub market_id: String,
@@ -20,38 +22,37 @@ impl OracleRequestMessage {
 }
 
 impl FromStr for OracleRequestMessage {
-    type Err = String;
+    type Err = OracleError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let err_string = "Failed to parse input message".to_string();
         let parts: Vec<&str> = s.split("##").collect();
 
-        if parts.len() != 5 {
-            Err(err_string)
+        if parts.len() != 4 {
+            Err(OracleError::InvalidMessageFormat)
         } else {
             let market_id = parts
                 .first()
                 .unwrap()
                 .parse()
-                .map_err(|_| "Could not parse the market id".to_string())?;
+                .map_err(|_| OracleError::InvalidMarketId)?;
 
             let nonce = parts
                 .get(1)
                 .unwrap()
                 .parse()
-                .map_err(|_| "Could not nonce the price".to_string())?;
+                .map_err(|_| OracleError::InvalidNonce)?;
 
             let public_key_bls = parts
                 .get(2)
                 .unwrap()
                 .parse()
-                .map_err(|_| "Could not parse the bls key".to_string())?;
+                .map_err(|_| OracleError::InvalidRequestPublicKey)?;
 
             let address = parts
                 .get(3)
                 .unwrap()
                 .parse()
-                .map_err(|_| "Could not parse the address".to_string())?;
+                .map_err(|_| OracleError::InvalidAddress)?;
 
             Ok(OracleRequestMessage {
                 market_id,
@@ -67,23 +68,23 @@ impl FromStr for OracleRequestMessage {
 mod price_message_tests {
     use scrypto::prelude::*;
 
-    use crate::price_message::OracleRequestMessage;
+    use crate::oracle_request_message::OracleRequestMessage;
 
     #[test]
     pub fn test_to_string() {
         let oracle_request_message = OracleRequestMessage {
-            market_id: "TEST:MARKET",
+            market_id: "TEST:MARKET".to_string(),
             nonce: 1,
-            public_key_bls: "abcTEST",
-            address: "defTEST"
+            public_key_bls: "abcTEST".to_string(),
+            address: "defTEST".to_string()
         };
 
-        assert_eq!(oracle_request_message.to_string(), "TEST:MARKET##1##abcTEST#defTEST");
+        assert_eq!(oracle_request_message.to_string(), "TEST:MARKET##1##abcTEST##defTEST");
     }
 
     #[test]
     pub fn from_string_test() {
-        let oracle_request_message = OracleRequestMessage::from_str("TEST:MARKET##1##abcTEST#defTEST").unwrap();
+        let oracle_request_message = OracleRequestMessage::from_str("TEST:MARKET##1##abcTEST##defTEST").unwrap();
         assert!(
             oracle_request_message.market_id == "TEST:MARKET"
                 && oracle_request_message.nonce == 1
@@ -93,4 +94,12 @@ mod price_message_tests {
 
         assert!(OracleRequestMessage::from_str("0-1000.234-1-1230-5").is_err())
     }
+
+    #[test]
+    pub fn test_field_count() {
+        // Messages have exactly four fields, five used to be required
+        assert!(OracleRequestMessage::from_str("TEST:MARKET##1##abcTEST##defTEST").is_ok());
+        assert!(OracleRequestMessage::from_str("TEST:MARKET##1##abcTEST").is_err());
+        assert!(OracleRequestMessage::from_str("TEST:MARKET##1##abcTEST##defTEST##extra").is_err());
+    }
 }
@@ -2,59 +2,119 @@ use std::str::FromStr;
 
 use scrypto::prelude::*;
 
+use crate::oracle_error::OracleError;
+
 #[derive(ScryptoSbor, Clone)]
 pub struct PriceMessage {
     pub market_id: String,
     pub price: Decimal,
     pub nonce: u64,
     pub created_at: u64,
+    pub bid: Decimal,
+    pub ask: Decimal,
+    /// Width of the confidence interval around `price`, in the same unit as the price.
+    pub confidence: Decimal,
+    /// Time after which the oracle no longer vouches for this price.
+    pub valid_until: u64,
 }
 
 impl PriceMessage {
     pub fn to_string(&self) -> String {
         return format!(
-            "{}-{}-{}-{}",
-            self.market_id, self.price, self.nonce, self.created_at
+            "{}-{}-{}-{}-{}-{}-{}-{}",
+            self.market_id,
+            self.price,
+            self.nonce,
+            self.created_at,
+            self.bid,
+            self.ask,
+            self.confidence,
+            self.valid_until
         );
     }
+
+    /// Whether both messages give a different price for the same market, nonce and creation time,
+    /// which a signer must never do.
+    ///
+    /// A message signed again later under the same nonce is not a conflict.
+    pub fn conflicts_with(&self, other: &PriceMessage) -> bool {
+        self.market_id == other.market_id
+            && self.nonce == other.nonce
+            && self.created_at == other.created_at
+            && self.price != other.price
+    }
 }
 
 impl FromStr for PriceMessage {
-    type Err = String;
+    type Err = OracleError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let err_string = "Failed to parse input message".to_string();
         let parts: Vec<&str> = s.split("-").collect();
 
-        if parts.len() != 4 {
-            Err(err_string)
+        if parts.len() != 8 {
+            Err(OracleError::InvalidMessageFormat)
         } else {
             let market_id = parts
                 .first()
                 .unwrap()
                 .parse()
-                .map_err(|_| "Could not parse the market id".to_string())?;
+                .map_err(|_| OracleError::InvalidMarketId)?;
 
             let price = Decimal::from_str(parts.get(1).unwrap())
-                .map_err(|_| "Could not parse the price".to_string())?;
+                .map_err(|_| OracleError::InvalidPrice)?;
 
             let nonce = parts
                 .get(2)
                 .unwrap()
                 .parse()
-                .map_err(|_| "Could not parse the nonce".to_string())?;
+                .map_err(|_| OracleError::InvalidNonce)?;
 
             let created_at = parts
                 .get(3)
                 .unwrap()
                 .parse()
-                .map_err(|_| "Could not parse the creation date".to_string())?;
+                .map_err(|_| OracleError::InvalidCreationDate)?;
+
+            let bid = Decimal::from_str(parts.get(4).unwrap())
+                .map_err(|_| OracleError::InvalidBid)?;
+
+            let ask = Decimal::from_str(parts.get(5).unwrap())
+                .map_err(|_| OracleError::InvalidAsk)?;
+
+            let confidence = Decimal::from_str(parts.get(6).unwrap())
+                .map_err(|_| OracleError::InvalidConfidence)?;
+
+            let valid_until = parts
+                .get(7)
+                .unwrap()
+                .parse()
+                .map_err(|_| OracleError::InvalidValidUntil)?;
+
+            if bid > ask {
+                return Err(OracleError::BidAboveAsk);
+            }
+
+            if price < bid || price > ask {
+                return Err(OracleError::PriceOutsideBidAsk);
+            }
+
+            if confidence < Decimal::ZERO {
+                return Err(OracleError::NegativeConfidence);
+            }
+
+            if valid_until < created_at {
+                return Err(OracleError::ExpiresBeforeCreation);
+            }
 
             Ok(PriceMessage {
                 market_id,
                 price,
                 nonce,
                 created_at,
+                bid,
+                ask,
+                confidence,
+                valid_until,
             })
         }
     }
@@ -69,25 +129,63 @@ mod price_message_tests {
     #[test]
     pub fn test_to_string() {
         let price_message = PriceMessage {
-            market_id: "TEST:MARKET",
+            market_id: "TEST:MARKET".to_string(),
             price: dec!(1000.234),
             nonce: 1,
             created_at: 1230,
+            bid: dec!(1000.2),
+            ask: dec!(1000.3),
+            confidence: dec!(0.05),
+            valid_until: 1290,
         };
 
-        assert_eq!(price_message.to_string(), "TEST:MARKET-1000.234-1-1230");
+        assert_eq!(
+            price_message.to_string(),
+            "TEST:MARKET-1000.234-1-1230-1000.2-1000.3-0.05-1290"
+        );
     }
 
     #[test]
     pub fn from_string_test() {
-        let price_message = PriceMessage::from_str("TEST:MARKET-1000.234-1-1230").unwrap();
+        let price_message =
+            PriceMessage::from_str("TEST:MARKET-1000.234-1-1230-1000.2-1000.3-0.05-1290").unwrap();
         assert!(
             price_message.market_id == "TEST:MARKET"
                 && price_message.price == dec!(1000.234)
                 && price_message.nonce == 1
                 && price_message.created_at == 1230
+                && price_message.bid == dec!(1000.2)
+                && price_message.ask == dec!(1000.3)
+                && price_message.confidence == dec!(0.05)
+                && price_message.valid_until == 1290
         );
 
-        assert!(PriceMessage::from_str("TEST-1000.234-1-1230-5").is_err())
+        assert!(PriceMessage::from_str("TEST-1000.234-1-1230-5").is_err());
+        assert!(PriceMessage::from_str("TEST-1000.234-1-1230-1000.3-1000.2-0.05-1290").is_err());
+        assert!(PriceMessage::from_str("TEST-1000.234-1-1230-1000.2-1000.3-0.05-1200").is_err());
+        assert!(PriceMessage::from_str("TEST-1000.4-1-1230-1000.2-1000.3-0.05-1290").is_err());
+        // The separator already keeps negative numbers out of the message
+        assert!(PriceMessage::from_str("TEST-1000.234-1-1230-1000.2-1000.3--0.05-1290").is_err());
+    }
+
+    #[test]
+    pub fn test_conflicts_with() {
+        let price_message =
+            PriceMessage::from_str("TEST:MARKET-1000.234-1-1230-1000.2-1000.3-0.05-1290").unwrap();
+
+        let other_price =
+            PriceMessage::from_str("TEST:MARKET-1000.25-1-1230-1000.2-1000.3-0.05-1290").unwrap();
+        let other_nonce =
+            PriceMessage::from_str("TEST:MARKET-1000.25-2-1230-1000.2-1000.3-0.05-1290").unwrap();
+        let other_market =
+            PriceMessage::from_str("TEST:OTHER-1000.25-1-1230-1000.2-1000.3-0.05-1290").unwrap();
+        let other_creation =
+            PriceMessage::from_str("TEST:MARKET-1000.25-1-1250-1000.2-1000.3-0.05-1290").unwrap();
+
+        assert!(price_message.conflicts_with(&other_price));
+        assert!(!price_message.conflicts_with(&price_message));
+        assert!(!price_message.conflicts_with(&other_nonce));
+        assert!(!price_message.conflicts_with(&other_market));
+        assert!(!price_message.conflicts_with(&other_creation));
     }
 }
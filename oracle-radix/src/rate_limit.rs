@@ -0,0 +1,114 @@
+use scrypto::prelude::*;
+
+/// Shortest window of a rate limit, the clock of the oracle only has minute precision.
+pub const MIN_RATE_LIMIT_WINDOW: u64 = 60;
+
+/// Rolling rate limit of a subscription.
+///
+/// The number of calls in the last `window` seconds is estimated from the calls of the current
+/// window plus the calls of the previous window, weighted by how much of it still overlaps.
+#[derive(ScryptoSbor, Clone, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    pub max_calls: u64,
+    pub window: u64,
+    pub window_start: u64,
+    pub window_calls: u64,
+    pub previous_window_calls: u64,
+}
+
+impl RateLimit {
+    pub fn new(max_calls: u64, window: u64, now: u64) -> Self {
+        RateLimit {
+            max_calls,
+            window,
+            window_start: now,
+            window_calls: 0,
+            previous_window_calls: 0,
+        }
+    }
+
+    /// Counts a call at `now`, returns `false` without counting it if the limit is reached.
+    pub fn try_call(&mut self, now: u64) -> bool {
+        self.roll(now);
+
+        if self.rolling_calls(now) >= self.max_calls {
+            return false;
+        }
+
+        self.window_calls += 1;
+        true
+    }
+
+    /// Whether the next call at `now` would be throttled.
+    pub fn is_exhausted(&self, now: u64) -> bool {
+        let mut rate_limit = self.clone();
+        rate_limit.roll(now);
+        rate_limit.rolling_calls(now) >= rate_limit.max_calls
+    }
+
+    /// Estimated number of calls made in the `window` seconds before `now`.
+    pub fn rolling_calls(&self, now: u64) -> u64 {
+        let elapsed = now.saturating_sub(self.window_start).min(self.window);
+        let previous_weight = (self.window - elapsed) as u128;
+        let previous_calls =
+            self.previous_window_calls as u128 * previous_weight / self.window as u128;
+
+        previous_calls as u64 + self.window_calls
+    }
+
+    fn roll(&mut self, now: u64) {
+        if now < self.window_start + self.window {
+            return;
+        }
+
+        let windows_passed = (now - self.window_start) / self.window;
+        self.previous_window_calls = if windows_passed == 1 {
+            self.window_calls
+        } else {
+            0
+        };
+        self.window_start += windows_passed * self.window;
+        self.window_calls = 0;
+    }
+}
+
+#[cfg(test)]
+mod rate_limit_tests {
+    use crate::rate_limit::RateLimit;
+
+    #[test]
+    pub fn test_limit_within_window() {
+        let mut rate_limit = RateLimit::new(2, 60, 1000);
+
+        assert!(rate_limit.try_call(1000));
+        assert!(!rate_limit.is_exhausted(1000));
+        assert!(rate_limit.try_call(1010));
+        assert!(rate_limit.is_exhausted(1010));
+        assert!(!rate_limit.try_call(1020));
+        assert_eq!(rate_limit.window_calls, 2);
+    }
+
+    #[test]
+    pub fn test_previous_window_is_weighted() {
+        let mut rate_limit = RateLimit::new(4, 60, 1000);
+        for _ in 0..4 {
+            assert!(rate_limit.try_call(1000));
+        }
+
+        // Half of the previous window still overlaps, so 2 of its 4 calls count
+        assert!(rate_limit.try_call(1090));
+        assert_eq!(rate_limit.rolling_calls(1090), 3);
+        assert!(rate_limit.try_call(1090));
+        assert!(!rate_limit.try_call(1090));
+    }
+
+    #[test]
+    pub fn test_limit_resets_after_idle_windows() {
+        let mut rate_limit = RateLimit::new(1, 60, 1000);
+
+        assert!(rate_limit.try_call(1000));
+        assert!(!rate_limit.try_call(1059));
+        assert!(rate_limit.try_call(1200));
+        assert_eq!(rate_limit.previous_window_calls, 0);
+    }
+}
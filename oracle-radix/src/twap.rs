@@ -0,0 +1,127 @@
+use scrypto::prelude::*;
+
+/// Number of observations kept per market, older observations are dropped first.
+pub const MAX_TWAP_OBSERVATIONS: usize = 256;
+
+#[derive(ScryptoSbor, Clone, Debug, PartialEq, Eq)]
+pub struct TwapObservation {
+    pub timestamp: u64,
+    pub price: Decimal,
+    /// Sum of price * seconds from the first observation up to `timestamp`.
+    pub price_cumulative: Decimal,
+}
+
+#[derive(ScryptoSbor, Clone, Default)]
+pub struct TwapAccumulator {
+    pub observations: Vec<TwapObservation>,
+}
+
+impl TwapAccumulator {
+    /// Records a new price at the given timestamp.
+    ///
+    /// Observations older than the last recorded one are ignored and `false` is returned.
+    pub fn record(&mut self, price: Decimal, timestamp: u64) -> bool {
+        let price_cumulative = match self.observations.last_mut() {
+            Some(last) if timestamp < last.timestamp => return false,
+            Some(last) if timestamp == last.timestamp => {
+                last.price = price;
+                return true;
+            }
+            Some(last) => {
+                last.price_cumulative + last.price * Decimal::from(timestamp - last.timestamp)
+            }
+            None => Decimal::ZERO,
+        };
+
+        if self.observations.len() == MAX_TWAP_OBSERVATIONS {
+            self.observations.remove(0);
+        }
+
+        self.observations.push(TwapObservation {
+            timestamp,
+            price,
+            price_cumulative,
+        });
+
+        true
+    }
+
+    /// Returns the time weighted average price over the `window` seconds before `now`.
+    ///
+    /// Returns `None` if there is no observation at or before the start of the window.
+    pub fn twap(&self, now: u64, window: u64) -> Option<Decimal> {
+        if window == 0 {
+            return None;
+        }
+
+        let start = now.checked_sub(window)?;
+        let cumulative_start = self.cumulative_at(start)?;
+        let cumulative_now = self.cumulative_at(now)?;
+
+        Some((cumulative_now - cumulative_start) / Decimal::from(window))
+    }
+
+    fn cumulative_at(&self, timestamp: u64) -> Option<Decimal> {
+        let observation = self
+            .observations
+            .iter()
+            .rev()
+            .find(|observation| observation.timestamp <= timestamp)?;
+
+        Some(
+            observation.price_cumulative
+                + observation.price * Decimal::from(timestamp - observation.timestamp),
+        )
+    }
+}
+
+#[cfg(test)]
+mod twap_tests {
+    use scrypto::prelude::*;
+
+    use crate::twap::{TwapAccumulator, MAX_TWAP_OBSERVATIONS};
+
+    #[test]
+    pub fn test_twap() {
+        let mut accumulator = TwapAccumulator::default();
+        accumulator.record(dec!(10), 1000);
+        accumulator.record(dec!(20), 1060);
+
+        // 60 seconds at 10, then 60 seconds at 20
+        assert_eq!(accumulator.twap(1120, 120), Some(dec!(15)));
+        // Only the last 60 seconds at 20
+        assert_eq!(accumulator.twap(1120, 60), Some(dec!(20)));
+    }
+
+    #[test]
+    pub fn test_twap_insufficient_observations() {
+        let mut accumulator = TwapAccumulator::default();
+        assert_eq!(accumulator.twap(1000, 60), None);
+
+        accumulator.record(dec!(10), 1000);
+        assert_eq!(accumulator.twap(1030, 60), None);
+        assert_eq!(accumulator.twap(1060, 0), None);
+    }
+
+    #[test]
+    pub fn test_record_ignores_older_observations() {
+        let mut accumulator = TwapAccumulator::default();
+        assert!(accumulator.record(dec!(10), 1000));
+        assert!(!accumulator.record(dec!(50), 900));
+        assert!(accumulator.record(dec!(12), 1000));
+
+        assert_eq!(accumulator.observations.len(), 1);
+        assert_eq!(accumulator.twap(1060, 60), Some(dec!(12)));
+    }
+
+    #[test]
+    pub fn test_record_drops_oldest_observations() {
+        let mut accumulator = TwapAccumulator::default();
+        for i in 0..(MAX_TWAP_OBSERVATIONS as u64 + 10) {
+            accumulator.record(dec!(1), i * 60);
+        }
+
+        assert_eq!(accumulator.observations.len(), MAX_TWAP_OBSERVATIONS);
+        assert_eq!(accumulator.observations.first().unwrap().timestamp, 600);
+    }
+}
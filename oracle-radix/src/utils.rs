@@ -1,24 +1,142 @@
 use std::str::FromStr;
 
-use scrypto::crypto::{Bls12381G1PublicKey, Bls12381G2Signature};
+use scrypto::crypto::{
+    Bls12381G1PublicKey, Bls12381G2Signature, Ed25519PublicKey, Ed25519Signature,
+    Secp256k1PublicKey, Secp256k1Signature,
+};
 use scrypto::crypto_utils::CryptoUtils;
-use scrypto::prelude::{Clock, Instant, TimePrecision};
+use scrypto::prelude::*;
+
+use crate::oracle_error::OracleError;
+
+#[derive(ScryptoSbor, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum OracleKeyType {
+    Bls12381,
+    Ed25519,
+    Secp256k1,
+}
+
+/// A public key allowed to sign oracle messages, together with its signature scheme.
+#[derive(ScryptoSbor, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum OraclePublicKey {
+    Bls12381(Bls12381G1PublicKey),
+    Ed25519(Ed25519PublicKey),
+    Secp256k1(Secp256k1PublicKey),
+}
+
+impl OraclePublicKey {
+    /// Parses a hex encoded public key of the given type, panics if it is not valid.
+    pub fn parse(key_type: OracleKeyType, public_key: &str) -> Self {
+        match key_type {
+            OracleKeyType::Bls12381 => Bls12381G1PublicKey::from_str(public_key)
+                .map(OraclePublicKey::Bls12381)
+                .ok(),
+            OracleKeyType::Ed25519 => Ed25519PublicKey::from_str(public_key)
+                .map(OraclePublicKey::Ed25519)
+                .ok(),
+            OracleKeyType::Secp256k1 => Secp256k1PublicKey::from_str(public_key)
+                .map(OraclePublicKey::Secp256k1)
+                .ok(),
+        }
+        .unwrap_or_else(|| panic!("{}", OracleError::InvalidPublicKey))
+    }
+
+    pub fn key_type(&self) -> OracleKeyType {
+        match self {
+            OraclePublicKey::Bls12381(_) => OracleKeyType::Bls12381,
+            OraclePublicKey::Ed25519(_) => OracleKeyType::Ed25519,
+            OraclePublicKey::Secp256k1(_) => OracleKeyType::Secp256k1,
+        }
+    }
+}
 
 pub fn get_time() -> u64 {
     let instant: Instant = Clock::current_time(TimePrecision::Minute);
     instant.seconds_since_unix_epoch.try_into().unwrap()
 }
 
-pub fn check_signature(message_str: &str, signature_str: &str, public_key: Bls12381G1PublicKey) {
-    let signature = match Bls12381G2Signature::from_str(signature_str) {
-        Ok(signature) => signature,
-        Err(err) => panic!(
-            "Error getting Bls12381G2Signature from str, error: {:?}",
-            err
-        ),
+/// Formats a unix timestamp as a UTC date for display, e.g. `2024-05-01 12:30 UTC`.
+pub fn format_utc_date(timestamp: u64) -> String {
+    let days = timestamp / 86_400;
+    let seconds_of_day = timestamp % 86_400;
+
+    // Civil date from the days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    let shifted_days = days + 719_468;
+    let era = shifted_days / 146_097;
+    let day_of_era = shifted_days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
     };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        seconds_of_day % 3_600 / 60
+    )
+}
+
+/// Tolerated clock difference for messages created ahead of the ledger time, which has minute precision.
+pub const MAX_CREATED_AT_DRIFT: u64 = 60;
+
+/// Checks that a signed message is inside its validity window.
+pub fn check_validity_window(created_at: u64, valid_until: u64) {
+    let now = get_time();
+    assert!(valid_until >= now, "{}", OracleError::PriceMessageExpired);
+    assert!(
+        created_at <= now + MAX_CREATED_AT_DRIFT,
+        "{}",
+        OracleError::PriceMessageFromTheFuture
+    );
+}
+
+/// Verifies a hex encoded signature of `message_str`.
+///
+/// Secp256k1 signatures are made over the Blake2b-256 hash of the message, the other schemes sign
+/// the message itself.
+pub fn verify_signature(message_str: &str, signature_str: &str, public_key: &OraclePublicKey) -> bool {
+    match public_key {
+        OraclePublicKey::Bls12381(public_key) => Bls12381G2Signature::from_str(signature_str)
+            .is_ok_and(|signature| {
+                CryptoUtils::bls12381_v1_verify(message_str.as_bytes(), public_key, signature)
+            }),
+        OraclePublicKey::Ed25519(public_key) => Ed25519Signature::from_str(signature_str)
+            .is_ok_and(|signature| {
+                CryptoUtils::ed25519_verify(message_str.as_bytes(), public_key, signature)
+            }),
+        OraclePublicKey::Secp256k1(public_key) => Secp256k1Signature::from_str(signature_str)
+            .is_ok_and(|signature| {
+                let hash = CryptoUtils::blake2b_256_hash(message_str.as_bytes());
+                CryptoUtils::secp256k1_ecdsa_verify(hash, public_key, signature)
+            }),
+    }
+}
+
+pub fn check_signature(message_str: &str, signature_str: &str, public_key: &OraclePublicKey) {
+    if !verify_signature(message_str, signature_str, public_key) {
+        panic!("{}", OracleError::SignatureVerificationFailed);
+    }
+}
+
+#[cfg(test)]
+mod utils_tests {
+    use crate::utils::format_utc_date;
 
-    if !CryptoUtils::bls12381_v1_verify(message_str.bytes().collect(), public_key, signature) {
-        panic!("Verification of signature failed!");
+    #[test]
+    pub fn test_format_utc_date() {
+        assert_eq!(format_utc_date(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_utc_date(951_782_400), "2000-02-29 00:00 UTC");
+        assert_eq!(format_utc_date(1_714_566_600), "2024-05-01 12:30 UTC");
+        assert_eq!(format_utc_date(4_102_444_799), "2099-12-31 23:59 UTC");
     }
 }
@@ -1,218 +1,2547 @@
 #[cfg(test)]
 mod trading_test {
+    use ed25519_dalek::{Signer, SigningKey};
     use test_engine::prelude::*;
 
-    use oracle_signature::oracle::OracleSubscription;
+    use oracle_signature::basket_price_message::BasketPriceMessage;
+    use oracle_signature::gumball::Status;
+    use oracle_signature::oracle::{
+        BillingMode, EquivocationReport, OracleConfig, OracleSubscription,
+        OracleSubscriptionExported, OracleSubscriptionGracePeriodCall, OracleSubscriptionLowCredit,
+        OracleSubscriptionUpdate, SignerStake, SubscriptionDelegate, SubscriptionDelegateRevoked,
+        SubscriptionInfo,
+    };
     use oracle_signature::price_message::PriceMessage;
+    use oracle_signature::utils::{OracleKeyType, OraclePublicKey};
 
     global_package!(TRADING, ".");
 
+    const SUBSCRIPTION_IMAGE_URL: &str = "https://morpher.com/subscription.png";
+
     pub fn sign(message: PriceMessage, secret_key: &Bls12381G1PrivateKey) -> String {
         secret_key
             .sign_v1(message.to_string().as_bytes())
             .to_string()
     }
 
-    pub fn instantiate_oracle() -> (TestEngine, Bls12381G1PrivateKey) {
-        let mut test_engine = TestEngine::new();
+    pub fn instantiate_oracle() -> (TestEngine, Bls12381G1PrivateKey) {
+        let mut test_engine = TestEngine::new();
+
+        let secret_key = Bls12381G1PrivateKey::from_u64(1).unwrap();
+
+        test_engine.add_global_package("morpher package", &TRADING);
+
+        test_engine.new_component(
+            "morpher oracle",
+            "MorpherOracle",
+            "instantiate",
+            env_args!(
+                secret_key.public_key().to_string(),
+                dec!("30"),
+                RoyaltyAmount::Xrd(dec!("1")),
+                Environment::Account("default"),
+                SUBSCRIPTION_IMAGE_URL.to_string()
+            ),
+        );
+
+        test_engine.call_faucet();
+
+        (test_engine, secret_key)
+    }
+
+    #[test]
+    fn test_new_subscription() {
+        let (mut test_engine, _) = instantiate_oracle();
+
+        let xrd_before = test_engine.current_balance("XRD");
+
+        test_engine
+            .call_method_builder(
+                "new_subscription",
+                env_args!(6u64, Fungible::Bucket("XRD", 190)),
+            )
+            .output("rtm/", "new_subscription")
+            .execute()
+            .assert_is_success();
+
+        let xrd_after = test_engine.current_balance("XRD");
+
+        // Check that only 180 XRD have been paid for 6 months
+        assert_eq!(xrd_before - xrd_after, dec!(180));
+
+        // Check that we received a subscription nft
+        let ids = test_engine.current_ids_balance("morpher subscription");
+
+        assert_eq!(ids.len(), 1);
+
+        // Check that subscriptions are numbered from 1
+        assert_eq!(ids.first().unwrap(), &NonFungibleLocalId::integer(1));
+
+        // Check that it has the right expiration time
+        let data: OracleSubscription =
+            test_engine.get_non_fungible_data("Morpher subscription", ids.first().unwrap().clone());
+
+        assert_eq!(data.expiration_time, 6 * 2_592_000);
+        assert_eq!(data.expiry_date, "1970-06-30 00:00 UTC");
+        assert_eq!(data.tier, "Monthly");
+        assert_eq!(data.key_image_url, Url::of(SUBSCRIPTION_IMAGE_URL));
+        assert!(!data.trial);
+        assert!(!data.complimentary);
+    }
+
+    #[test]
+    fn test_empty_subscription_image_url_fails() {
+        let (mut test_engine, _) = instantiate_oracle();
+
+        call_admin_method(
+            &mut test_engine,
+            "set_subscription_image_url",
+            env_args!("".to_string()),
+        )
+        .assert_failed_with("[ORACLE-424]");
+    }
+
+    #[test]
+    fn test_new_zero_month_subscription_fails() {
+        let (mut test_engine, _) = instantiate_oracle();
+
+        test_engine
+            .call_method(
+                "new_subscription",
+                env_args!(0u64, Fungible::Bucket("XRD", 190)),
+            )
+            .assert_failed_with("Cannot add 0 months to the subscription!");
+    }
+
+    #[test]
+    fn test_new_subscription_with_other_token_fails() {
+        let (mut test_engine, _) = instantiate_oracle();
+
+        test_engine.new_token("test token", 1000);
+        test_engine
+            .call_method(
+                "new_subscription",
+                env_args!(6u64, Fungible::Bucket("test token", 190)),
+            )
+            .assert_failed_with("The payment should be made in XRD tokens!");
+    }
+
+    #[test]
+    fn test_new_subscription_with_not_enough_tokens_fails() {
+        let (mut test_engine, _) = instantiate_oracle();
+
+        test_engine
+            .call_method(
+                "new_subscription",
+                env_args!(6u64, Fungible::Bucket("XRD", 170)),
+            )
+            .assert_failed_with(
+                "For 6 months, the expected payment is 180 XRD (only supplied 170)",
+            );
+    }
+
+    #[test]
+    fn test_new_metered_subscription_unavailable_fails() {
+        let (mut test_engine, _) = instantiate_oracle();
+
+        test_engine
+            .call_method(
+                "new_metered_subscription",
+                env_args!(Fungible::Bucket("XRD", 10)),
+            )
+            .assert_failed_with("Pay per call subscriptions are not available");
+    }
+
+    #[test]
+    fn test_get_twap_without_observations_fails() {
+        let (mut test_engine, _) = instantiate_oracle();
+
+        test_engine
+            .call_method(
+                "get_twap",
+                env_args!("GATEIO:XRD_USDT".to_string(), 3600u64),
+            )
+            .assert_failed_with("Not enough price observations for the requested TWAP window");
+    }
+
+    #[test]
+    fn test_metered_subscription_is_charged_per_call() {
+        let (mut test_engine, secret_key) = instantiate_oracle();
+
+        call_admin_method(
+            &mut test_engine,
+            "set_metered_billing",
+            env_args!(Some(dec!(1)), dec!(5)),
+        )
+        .assert_is_success();
+
+        test_engine
+            .call_method(
+                "new_metered_subscription",
+                env_args!(Fungible::Bucket("XRD", 7)),
+            )
+            .assert_is_success();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        let mut low_credit_events = Vec::new();
+        for nonce in 0..3u64 {
+            let message = price_message(dec!(1), nonce, 0);
+            let receipt = test_engine.call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids.clone())
+                ),
+            );
+
+            low_credit_events.extend(
+                receipt
+                    .expect_commit_success()
+                    .application_events
+                    .iter()
+                    .filter_map(|(_, event)| {
+                        scrypto_decode::<OracleSubscriptionLowCredit>(event).ok()
+                    })
+                    .map(|event| (nonce, event.credit)),
+            );
+        }
+
+        // The credit only fell below the threshold of 5 XRD on the third call
+        assert_eq!(low_credit_events, vec![(2, dec!(4))]);
+
+        let credit: Decimal = test_engine
+            .call_method(
+                "get_subscription_credit",
+                env_args!(ids.first().unwrap().clone()),
+            )
+            .expect_commit_success()
+            .output(1);
+        assert_eq!(credit, dec!(4));
+
+        let remaining_calls: u64 = test_engine
+            .call_method("remaining_calls", env_args!(ids.first().unwrap().clone()))
+            .expect_commit_success()
+            .output(1);
+        assert_eq!(remaining_calls, 4);
+    }
+
+    pub fn instantiate_with_subscription() -> (TestEngine, Bls12381G1PrivateKey) {
+        let (mut test_engine, secret_key) = instantiate_oracle();
+
+        test_engine
+            .call_method_builder(
+                "new_subscription",
+                env_args!(6u64, Fungible::Bucket("XRD", 190)),
+            )
+            .output("rtm/", "new_subscription")
+            .execute()
+            .assert_is_success();
+
+        (test_engine, secret_key)
+    }
+
+    pub fn price_message(price: Decimal, nonce: u64, created_at: u64) -> PriceMessage {
+        PriceMessage {
+            market_id: "GATEIO:XRD_USDT".to_string(),
+            price,
+            nonce,
+            created_at,
+            bid: price,
+            ask: price,
+            confidence: dec!(0),
+            valid_until: u64::MAX,
+        }
+    }
+
+    pub fn instantiate_with_deviation_guard() -> (TestEngine, Bls12381G1PrivateKey) {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+
+        test_engine
+            .call_method_builder(
+                "set_price_deviation_guard",
+                env_args!(Some(dec!(10)), 3600u64),
+            )
+            .with_badge("Oracle admin badge")
+            .execute()
+            .assert_is_success();
+
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+        let reference_price = price_message(dec!(1), 0, 0);
+
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    reference_price.to_string(),
+                    sign(reference_price, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_is_success();
+
+        (test_engine, secret_key)
+    }
+
+    #[test]
+    fn test_price_deviation_guard() {
+        let (mut test_engine, secret_key) = instantiate_with_deviation_guard();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        let small_move = price_message(dec!("1.05"), 1, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    small_move.to_string(),
+                    sign(small_move, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids.clone())
+                ),
+            )
+            .assert_is_success();
+
+        // 1.5 deviates about 43% from the reference price of 1.05
+        let large_move = price_message(dec!("1.5"), 2, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    large_move.to_string(),
+                    sign(large_move, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_failed_with("[ORACLE-303]");
+    }
+
+    #[test]
+    fn test_price_after_deviation_window_resets_reference() {
+        let (mut test_engine, secret_key) = instantiate_with_deviation_guard();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        test_engine.advance_time(7200);
+
+        // The reference of the quiet market is too old, so a large move is accepted
+        let later_price = price_message(dec!(2), 1, 7200);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    later_price.to_string(),
+                    sign(later_price, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids.clone())
+                ),
+            )
+            .assert_is_success();
+
+        // and becomes the new reference of the guard
+        let next_price = price_message(dec!(1), 2, 7200);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    next_price.to_string(),
+                    sign(next_price, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids.clone())
+                ),
+            )
+            .assert_failed_with("[ORACLE-303]");
+
+        // Messages older than the window before the reference cannot be compared
+        let old_price = price_message(dec!(2), 3, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    old_price.to_string(),
+                    sign(old_price, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_failed_with("[ORACLE-311]");
+    }
+
+    #[test]
+    fn test_emergency_price_input_bypasses_deviation_guard() {
+        let (mut test_engine, secret_key) = instantiate_with_deviation_guard();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        let emergency_key = Bls12381G1PrivateKey::from_u64(2).unwrap();
+        test_engine
+            .call_method_builder(
+                "set_emergency_pub_key",
+                env_args!(Some((
+                    OracleKeyType::Bls12381,
+                    emergency_key.public_key().to_string()
+                ))),
+            )
+            .with_badge("Oracle admin badge")
+            .execute()
+            .assert_is_success();
+
+        let large_move = price_message(dec!(2), 1, 0);
+
+        // Without a valid co-signature the large move is rejected
+        test_engine
+            .call_method(
+                "check_emergency_price_input",
+                env_args!(
+                    large_move.to_string(),
+                    sign(large_move.clone(), &secret_key),
+                    sign(large_move.clone(), &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids.clone())
+                ),
+            )
+            .assert_failed_with("[ORACLE-200]");
+
+        test_engine
+            .call_method(
+                "check_emergency_price_input",
+                env_args!(
+                    large_move.to_string(),
+                    sign(large_move.clone(), &secret_key),
+                    sign(large_move, &emergency_key),
+                    NonFungible::Proof("Morpher subscription", ids.clone())
+                ),
+            )
+            .assert_is_success();
+
+        // The co-signed price is the new reference of the guard
+        let next_price = price_message(dec!("2.1"), 2, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    next_price.to_string(),
+                    sign(next_price, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_is_success();
+    }
+
+    #[test]
+    fn test_check_price_input_with_max_confidence() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        let banded_price = |nonce: u64| PriceMessage {
+            bid: dec!("0.99"),
+            ask: dec!("1.01"),
+            confidence: dec!("0.02"),
+            ..price_message(dec!(1), nonce, 0)
+        };
+
+        let message = banded_price(0);
+        let accepted: PriceMessage = test_engine
+            .call_method(
+                "check_price_input_with_max_confidence",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    dec!("0.05"),
+                    NonFungible::Proof("Morpher subscription", ids.clone())
+                ),
+            )
+            .expect_commit_success()
+            .output(1);
+        assert_eq!(accepted.confidence, dec!("0.02"));
+
+        let message = banded_price(1);
+        test_engine
+            .call_method(
+                "check_price_input_with_max_confidence",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    dec!("0.01"),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_failed_with("[ORACLE-304]");
+    }
+
+    #[test]
+    fn test_get_twap_after_accepted_prices() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        for (nonce, price) in [dec!(1), dec!(2)].into_iter().enumerate() {
+            let created_at = nonce as u64 * 600;
+            let message = price_message(price, nonce as u64, created_at);
+
+            test_engine
+                .call_method(
+                    "check_price_input",
+                    env_args!(
+                        message.to_string(),
+                        sign(message, &secret_key),
+                        NonFungible::Proof("Morpher subscription", ids.clone())
+                    ),
+                )
+                .assert_is_success();
+
+            test_engine.advance_time(600);
+        }
+
+        // 1 during the first 10 minutes and 2 during the last 10 minutes
+        let receipt = test_engine.call_method(
+            "get_twap",
+            env_args!("GATEIO:XRD_USDT".to_string(), 1200u64),
+        );
+        let twap: Decimal = receipt.expect_commit_success().output(1);
+        assert_eq!(twap, dec!("1.5"));
+
+        let receipt = test_engine.call_method(
+            "get_twap",
+            env_args!("GATEIO:XRD_USDT".to_string(), 600u64),
+        );
+        let twap: Decimal = receipt.expect_commit_success().output(1);
+        assert_eq!(twap, dec!(2));
+    }
+
+    pub fn add_signer(test_engine: &mut TestEngine, key_type: OracleKeyType, public_key: String) {
+        test_engine
+            .call_method_builder("add_signer", env_args!(key_type, public_key))
+            .with_badge("Oracle admin badge")
+            .execute()
+            .assert_is_success();
+    }
+
+    #[test]
+    fn test_ed25519_signer() {
+        let (mut test_engine, _) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        add_signer(
+            &mut test_engine,
+            OracleKeyType::Ed25519,
+            Ed25519PublicKey(signing_key.verifying_key().to_bytes()).to_string(),
+        );
+
+        // Ed25519 signers sign the message itself
+        let message = price_message(dec!(1), 0, 0);
+        let signature = Ed25519Signature(signing_key.sign(message.to_string().as_bytes()).to_bytes());
+
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    signature.to_string(),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_is_success();
+    }
+
+    #[test]
+    fn test_secp256k1_signer() {
+        let (mut test_engine, _) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        let secp256k1_key = Secp256k1PrivateKey::from_u64(1).unwrap();
+        add_signer(
+            &mut test_engine,
+            OracleKeyType::Secp256k1,
+            secp256k1_key.public_key().to_string(),
+        );
+
+        // Secp256k1 signers sign the Blake2b-256 hash of the message
+        let message = price_message(dec!(1), 0, 0);
+        let signature = secp256k1_key.sign(&hash(message.to_string().as_bytes()));
+
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    signature.to_string(),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_is_success();
+    }
+
+    #[test]
+    fn test_add_and_remove_signers() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        let new_key = Bls12381G1PrivateKey::from_u64(2).unwrap();
+        add_signer(
+            &mut test_engine,
+            OracleKeyType::Bls12381,
+            new_key.public_key().to_string(),
+        );
+
+        test_engine
+            .call_method_builder(
+                "add_signer",
+                env_args!(OracleKeyType::Bls12381, new_key.public_key().to_string()),
+            )
+            .with_badge("Oracle admin badge")
+            .execute()
+            .assert_failed_with("[ORACLE-202]");
+
+        test_engine
+            .call_method_builder(
+                "remove_signer",
+                env_args!(OracleKeyType::Bls12381, secret_key.public_key().to_string()),
+            )
+            .with_badge("Oracle admin badge")
+            .execute()
+            .assert_is_success();
+
+        // Messages of the removed key are rejected, the new key is accepted
+        let message = price_message(dec!(1), 0, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message.clone(), &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids.clone())
+                ),
+            )
+            .assert_failed_with("[ORACLE-200]");
+
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &new_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_is_success();
+
+        test_engine
+            .call_method_builder(
+                "remove_signer",
+                env_args!(OracleKeyType::Bls12381, new_key.public_key().to_string()),
+            )
+            .with_badge("Oracle admin badge")
+            .execute()
+            .assert_failed_with("[ORACLE-204]");
+    }
+
+    pub fn call_admin_method(
+        test_engine: &mut TestEngine,
+        method: &str,
+        args: Vec<Box<dyn ToEncode>>,
+    ) -> TransactionReceipt {
+        test_engine
+            .call_method_builder(method, args)
+            .with_badge("Oracle admin badge")
+            .execute()
+    }
+
+    pub fn update_metadata(
+        test_engine: &mut TestEngine,
+        component: &str,
+        badge: &str,
+        update: impl FnOnce(ManifestBuilder, ComponentAddress) -> ManifestBuilder,
+    ) -> TransactionReceipt {
+        let component_address = test_engine.get_component(component);
+        test_engine
+            .build_call()
+            .with_badge(badge)
+            .with_manifest_builder(|builder| update(builder, component_address))
+            .execute()
+    }
+
+    #[test]
+    fn test_admin_badge_sets_and_locks_oracle_metadata() {
+        let (mut test_engine, _) = instantiate_with_subscription();
+
+        update_metadata(
+            &mut test_engine,
+            "morpher oracle",
+            "Morpher subscription",
+            |builder, oracle| builder.set_metadata(oracle, "name", "Renamed Oracle".to_string()),
+        )
+        .assert_failed_with("Unauthorized");
+
+        update_metadata(
+            &mut test_engine,
+            "morpher oracle",
+            "Oracle admin badge",
+            |builder, oracle| builder.set_metadata(oracle, "name", "Renamed Oracle".to_string()),
+        )
+        .assert_is_success();
+
+        update_metadata(
+            &mut test_engine,
+            "morpher oracle",
+            "Oracle admin badge",
+            |builder, oracle| builder.lock_metadata(oracle, "name"),
+        )
+        .assert_is_success();
+
+        update_metadata(
+            &mut test_engine,
+            "morpher oracle",
+            "Oracle admin badge",
+            |builder, oracle| builder.set_metadata(oracle, "name", "Morpher Oracle".to_string()),
+        )
+        .assert_failed_with("Locked");
+    }
+
+    #[test]
+    fn test_new_trial_subscription_unavailable_fails() {
+        let (mut test_engine, _) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        test_engine
+            .call_method(
+                "new_trial_subscription",
+                env_args!(NonFungible::Proof("Morpher subscription", ids)),
+            )
+            .assert_failed_with("[ORACLE-405]");
+    }
+
+    #[test]
+    fn test_new_trial_subscription_needs_an_account_badge() {
+        let (mut test_engine, _) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        call_admin_method(&mut test_engine, "set_trial_config", env_args!(7u64, 1000u64))
+            .assert_is_success();
+
+        // Only the owner badge of an account can claim a trial
+        test_engine
+            .call_method(
+                "new_trial_subscription",
+                env_args!(NonFungible::Proof("Morpher subscription", ids)),
+            )
+            .assert_failed_with("InvalidProof");
+    }
+
+    pub fn instantiate_with_account_badge() -> (TestEngine, Bls12381G1PrivateKey) {
+        let (mut test_engine, secret_key) = instantiate_oracle();
+        let account = test_engine.current_account_address();
+
+        // Securifying the account hands out its owner badge, which trials are claimed with
+        test_engine
+            .build_call()
+            .with_manifest_builder(|builder| {
+                builder
+                    .call_method(account, "securify", manifest_args!())
+                    .deposit_batch(account)
+            })
+            .execute()
+            .assert_is_success();
+
+        (test_engine, secret_key)
+    }
+
+    pub fn claim_trial(test_engine: &mut TestEngine) -> TransactionReceipt {
+        let account = test_engine.current_account_address();
+        let oracle = test_engine.get_component("morpher oracle");
+        let account_badge_id = NonFungibleLocalId::bytes(account.as_node_id().0).unwrap();
+
+        test_engine
+            .build_call()
+            .with_manifest_builder(|builder| {
+                builder
+                    .create_proof_from_account_of_non_fungibles(
+                        account,
+                        ACCOUNT_OWNER_BADGE,
+                        [account_badge_id],
+                    )
+                    .pop_from_auth_zone("account_badge")
+                    .call_method_with_name_lookup(oracle, "new_trial_subscription", |lookup| {
+                        (lookup.proof("account_badge"),)
+                    })
+                    .deposit_batch(account)
+            })
+            .execute()
+    }
+
+    #[test]
+    fn test_new_trial_subscription() {
+        let (mut test_engine, secret_key) = instantiate_with_account_badge();
+
+        call_admin_method(
+            &mut test_engine,
+            "set_trial_config",
+            env_args!(7u64, 1000u64),
+        )
+        .assert_is_success();
+
+        claim_trial(&mut test_engine).assert_is_success();
+
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+        assert_eq!(ids.len(), 1);
+        let data: OracleSubscription =
+            test_engine.get_non_fungible_data("Morpher subscription", ids.first().unwrap().clone());
+        assert!(data.trial);
+        assert_eq!(data.max_nonce, 1000);
+        assert_eq!(data.expiration_time, 7 * 86_400);
+
+        let message = price_message(dec!(1), 0, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_is_success();
+    }
+
+    #[test]
+    fn test_new_trial_subscription_once_per_account() {
+        let (mut test_engine, _) = instantiate_with_account_badge();
+
+        call_admin_method(
+            &mut test_engine,
+            "set_trial_config",
+            env_args!(7u64, 1000u64),
+        )
+        .assert_is_success();
+
+        claim_trial(&mut test_engine).assert_is_success();
+        claim_trial(&mut test_engine).assert_failed_with("[ORACLE-406]");
+
+        assert_eq!(
+            test_engine
+                .current_ids_balance("Morpher subscription")
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_call_during_grace_period() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        call_admin_method(&mut test_engine, "set_grace_period", env_args!(86_400u64, 1u64))
+            .assert_is_success();
+
+        test_engine.advance_time(2_592_000 * 6 + 60);
+
+        let message = price_message(dec!(1), 0, 2_592_000 * 6 + 60);
+        let receipt = test_engine.call_method(
+            "check_price_input",
+            env_args!(
+                message.to_string(),
+                sign(message, &secret_key),
+                NonFungible::Proof("Morpher subscription", ids.clone())
+            ),
+        );
+
+        let grace_period_call = receipt
+            .expect_commit_success()
+            .application_events
+            .iter()
+            .find_map(|(_, event)| {
+                scrypto_decode::<OracleSubscriptionGracePeriodCall>(event).ok()
+            })
+            .unwrap();
+        assert_eq!(grace_period_call.nft_id, ids.first().unwrap().clone());
+        assert_eq!(grace_period_call.grace_calls_left, 0);
+
+        let data: OracleSubscription = test_engine
+            .get_non_fungible_data("Morpher subscription", ids.first().unwrap().clone());
+        assert_eq!(data.grace_calls, 1);
+
+        // The grace period only allows a single call
+        let message = price_message(dec!(1), 1, 2_592_000 * 6 + 60);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_failed_with("Subscription has expired!");
+    }
+
+    #[test]
+    fn test_get_config() {
+        let (mut test_engine, secret_key) = instantiate_oracle();
+
+        let config: OracleConfig = test_engine
+            .call_method("get_config", env_args!())
+            .expect_commit_success()
+            .output(1);
+
+        assert_eq!(config.signers.len(), 1);
+        assert_eq!(
+            config.signers.first().unwrap().key_type(),
+            OracleKeyType::Bls12381
+        );
+        assert_eq!(
+            config.signers.first().unwrap(),
+            &OraclePublicKey::parse(
+                OracleKeyType::Bls12381,
+                &secret_key.public_key().to_string()
+            )
+        );
+        assert_eq!(config.monthly_subscription_fee, dec!(30));
+        assert_eq!(config.max_price_deviation, None);
+        assert_eq!(config.trial_days, 0);
+    }
+
+    #[test]
+    fn test_get_subscription() {
+        let (mut test_engine, _) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        test_engine.advance_time(2_592_000);
+
+        let info: SubscriptionInfo = test_engine
+            .call_method("get_subscription", env_args!(ids.first().unwrap().clone()))
+            .expect_commit_success()
+            .output(1);
+
+        assert_eq!(info.subscription.tier, "Monthly");
+        assert_eq!(info.remaining_time, 5 * 2_592_000);
+        assert_eq!(info.remaining_calls, 6_000_000);
+        assert_eq!(info.credit, dec!(0));
+
+        let remaining_time: u64 = test_engine
+            .call_method("remaining_time", env_args!(ids.first().unwrap().clone()))
+            .expect_commit_success()
+            .output(1);
+        assert_eq!(remaining_time, 5 * 2_592_000);
+
+        let remaining_calls: u64 = test_engine
+            .call_method("remaining_calls", env_args!(ids.first().unwrap().clone()))
+            .expect_commit_success()
+            .output(1);
+        assert_eq!(remaining_calls, 6_000_000);
+
+        // Both are 0 once the subscription expired
+        test_engine.advance_time(5 * 2_592_000 + 60);
+
+        let info: SubscriptionInfo = test_engine
+            .call_method("get_subscription", env_args!(ids.first().unwrap().clone()))
+            .expect_commit_success()
+            .output(1);
+
+        assert_eq!(info.remaining_time, 0);
+        assert_eq!(info.remaining_calls, 0);
+    }
+
+    #[test]
+    fn test_is_nonce_used() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        let is_nonce_used: bool = test_engine
+            .call_method("is_nonce_used", env_args!(5u64))
+            .expect_commit_success()
+            .output(1);
+        assert!(!is_nonce_used);
+
+        let message = price_message(dec!(1), 5, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_is_success();
+
+        let is_nonce_used: bool = test_engine
+            .call_method("is_nonce_used", env_args!(5u64))
+            .expect_commit_success()
+            .output(1);
+        assert!(is_nonce_used);
+    }
+
+    #[test]
+    fn test_derived_nonce_is_rejected() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        let message = price_message(dec!(1), u64::MAX, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_failed_with("[ORACLE-313]");
+    }
+
+    #[test]
+    fn test_check_basket_price_input() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        let basket_price_message = BasketPriceMessage {
+            prices: vec![
+                ("GATEIO:XRD_USDT".to_string(), dec!("0.0123")),
+                ("BINANCE:BTC_USDT".to_string(), dec!("64000.5")),
+            ],
+            nonce: 0,
+            created_at: 0,
+            valid_until: u64::MAX,
+        };
+        let signature = secret_key
+            .sign_v1(basket_price_message.to_string().as_bytes())
+            .to_string();
+
+        let prices: HashMap<String, Decimal> = test_engine
+            .call_method(
+                "check_basket_price_input",
+                env_args!(
+                    basket_price_message.to_string(),
+                    signature,
+                    NonFungible::Proof("Morpher subscription", ids.clone())
+                ),
+            )
+            .expect_commit_success()
+            .output(1);
+
+        assert_eq!(prices.len(), 2);
+        assert_eq!(prices.get("GATEIO:XRD_USDT"), Some(&dec!("0.0123")));
+        assert_eq!(prices.get("BINANCE:BTC_USDT"), Some(&dec!("64000.5")));
+
+        // The basket consumed its nonce for single price messages too
+        let message = price_message(dec!("0.0123"), 0, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_failed_with("This nonce has already been used");
+    }
+
+    #[test]
+    fn test_check_basket_price_input_with_zero_price_fails() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        let message = "GATEIO:XRD_USDT=0.0123,BINANCE:BTC_USDT=0-0-0-60".to_string();
+        let signature = secret_key.sign_v1(message.as_bytes()).to_string();
+
+        test_engine
+            .call_method(
+                "check_basket_price_input",
+                env_args!(
+                    message,
+                    signature,
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_failed_with("[ORACLE-120]");
+    }
+
+    #[test]
+    fn test_rate_limited_call_fails() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        test_engine
+            .call_method(
+                "set_subscription_rate_limit",
+                env_args!(
+                    NonFungible::Proof("Morpher subscription", ids.clone()),
+                    Some((1u64, 30u64))
+                ),
+            )
+            .assert_failed_with("[ORACLE-409]");
+
+        test_engine
+            .call_method(
+                "set_subscription_rate_limit",
+                env_args!(
+                    NonFungible::Proof("Morpher subscription", ids.clone()),
+                    Some((1u64, 3600u64))
+                ),
+            )
+            .assert_is_success();
+
+        let message = price_message(dec!(1), 0, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids.clone())
+                ),
+            )
+            .assert_is_success();
+
+        let message = price_message(dec!(1), 1, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_failed_with("[ORACLE-408]");
+    }
+
+    pub fn instantiate_with_staked_signer() -> (TestEngine, Bls12381G1PrivateKey, Bls12381G1PrivateKey)
+    {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+
+        call_admin_method(
+            &mut test_engine,
+            "set_signer_staking",
+            env_args!(Some(dec!(100)), 3600u64),
+        )
+        .assert_is_success();
+
+        let staked_key = Bls12381G1PrivateKey::from_u64(3).unwrap();
+        let public_key = staked_key.public_key().to_string();
+        let ownership_signature = staked_key
+            .sign_v1(format!("stake##{}", public_key).as_bytes())
+            .to_string();
+
+        test_engine
+            .call_method(
+                "stake_signer",
+                env_args!(
+                    OracleKeyType::Bls12381,
+                    public_key,
+                    ownership_signature,
+                    Fungible::Bucket("XRD", 100)
+                ),
+            )
+            .assert_is_success();
+
+        (test_engine, secret_key, staked_key)
+    }
+
+    pub fn instantiate_with_approved_signer(
+    ) -> (TestEngine, Bls12381G1PrivateKey, Bls12381G1PrivateKey) {
+        let (mut test_engine, secret_key, staked_key) = instantiate_with_staked_signer();
+
+        call_admin_method(
+            &mut test_engine,
+            "approve_staked_signer",
+            env_args!(OracleKeyType::Bls12381, staked_key.public_key().to_string()),
+        )
+        .assert_is_success();
+
+        (test_engine, secret_key, staked_key)
+    }
+
+    #[test]
+    fn test_staked_signer_needs_approval() {
+        let (mut test_engine, _, staked_key) = instantiate_with_staked_signer();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        let message = price_message(dec!(1), 0, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message.clone(), &staked_key),
+                    NonFungible::Proof("Morpher subscription", ids.clone())
+                ),
+            )
+            .assert_failed_with("[ORACLE-200]");
+
+        // Only the admin approves staked keys
+        test_engine
+            .call_method(
+                "approve_staked_signer",
+                env_args!(OracleKeyType::Bls12381, staked_key.public_key().to_string()),
+            )
+            .assert_failed_with("Unauthorized");
+
+        call_admin_method(
+            &mut test_engine,
+            "approve_staked_signer",
+            env_args!(OracleKeyType::Bls12381, staked_key.public_key().to_string()),
+        )
+        .assert_is_success();
+
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &staked_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_is_success();
+
+        call_admin_method(
+            &mut test_engine,
+            "approve_staked_signer",
+            env_args!(OracleKeyType::Bls12381, staked_key.public_key().to_string()),
+        )
+        .assert_failed_with("[ORACLE-202]");
+    }
+
+    #[test]
+    fn test_stake_signer_below_minimum_fails() {
+        let (mut test_engine, _) = instantiate_with_subscription();
+
+        call_admin_method(
+            &mut test_engine,
+            "set_signer_staking",
+            env_args!(Some(dec!(100)), 3600u64),
+        )
+        .assert_is_success();
+
+        let staked_key = Bls12381G1PrivateKey::from_u64(3).unwrap();
+        let public_key = staked_key.public_key().to_string();
+        let ownership_signature = staked_key
+            .sign_v1(format!("stake##{}", public_key).as_bytes())
+            .to_string();
+
+        test_engine
+            .call_method(
+                "stake_signer",
+                env_args!(
+                    OracleKeyType::Bls12381,
+                    public_key,
+                    ownership_signature,
+                    Fungible::Bucket("XRD", 50)
+                ),
+            )
+            .assert_failed_with("[ORACLE-501]");
+    }
+
+    #[test]
+    fn test_stake_unstake_and_withdraw() {
+        let (mut test_engine, _, staked_key) = instantiate_with_approved_signer();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+        let stake_ids = test_engine.current_ids_balance("Morpher signer stake");
+        assert_eq!(stake_ids.len(), 1);
+
+        // The approved staked key is a signer
+        let message = price_message(dec!(1), 0, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &staked_key),
+                    NonFungible::Proof("Morpher subscription", ids.clone())
+                ),
+            )
+            .assert_is_success();
+
+        test_engine
+            .call_method(
+                "withdraw_signer_stake",
+                env_args!(NonFungible::Bucket("Morpher signer stake", stake_ids.clone())),
+            )
+            .assert_failed_with("[ORACLE-504]");
+
+        test_engine
+            .call_method(
+                "unstake_signer",
+                env_args!(NonFungible::Proof("Morpher signer stake", stake_ids.clone())),
+            )
+            .assert_is_success();
+
+        // Once unstaked its messages are rejected
+        let message = price_message(dec!(1), 1, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &staked_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_failed_with("[ORACLE-200]");
+
+        test_engine
+            .call_method(
+                "withdraw_signer_stake",
+                env_args!(NonFungible::Bucket("Morpher signer stake", stake_ids.clone())),
+            )
+            .assert_failed_with("[ORACLE-505]");
+
+        test_engine.advance_time(3600);
+
+        let xrd_before = test_engine.current_balance("XRD");
+        test_engine
+            .call_method(
+                "withdraw_signer_stake",
+                env_args!(NonFungible::Bucket("Morpher signer stake", stake_ids)),
+            )
+            .assert_is_success();
+
+        assert_eq!(test_engine.current_balance("XRD") - xrd_before, dec!(100));
+        assert!(test_engine
+            .current_ids_balance("Morpher signer stake")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_slash_signer() {
+        let (mut test_engine, _, staked_key) = instantiate_with_staked_signer();
+        let stake_ids = test_engine.current_ids_balance("Morpher signer stake");
+
+        let message_a = price_message(dec!(1), 7, 0);
+        let message_b = price_message(dec!(2), 7, 0);
+
+        let xrd_before = test_engine.current_balance("XRD");
+        call_admin_method(
+            &mut test_engine,
+            "slash_signer",
+            env_args!(
+                OracleKeyType::Bls12381,
+                staked_key.public_key().to_string(),
+                message_a.to_string(),
+                sign(message_a, &staked_key),
+                message_b.to_string(),
+                sign(message_b, &staked_key)
+            ),
+        )
+        .assert_is_success();
+
+        // The admin received the stake
+        assert_eq!(test_engine.current_balance("XRD") - xrd_before, dec!(100));
+
+        let stake: SignerStake = test_engine
+            .get_non_fungible_data("Morpher signer stake", stake_ids.first().unwrap().clone());
+        assert_eq!(stake.amount, dec!(0));
+        assert!(stake.unbonding_until.is_some());
+
+        // The emptied stake can be withdrawn at once to burn its NFT
+        test_engine
+            .call_method(
+                "withdraw_signer_stake",
+                env_args!(NonFungible::Bucket("Morpher signer stake", stake_ids)),
+            )
+            .assert_is_success();
+
+        assert!(test_engine
+            .current_ids_balance("Morpher signer stake")
+            .is_empty());
+    }
+
+    #[test]
+    fn test_slash_signer_for_wrong_price() {
+        let (mut test_engine, _, staked_key) = instantiate_with_approved_signer();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+        let stake_ids = test_engine.current_ids_balance("Morpher signer stake");
+
+        let wrong_message = price_message(dec!(1000), 7, 0);
+
+        // Only the admin proves a price wrong
+        test_engine
+            .call_method(
+                "slash_signer_for_wrong_price",
+                env_args!(
+                    OracleKeyType::Bls12381,
+                    staked_key.public_key().to_string(),
+                    wrong_message.to_string(),
+                    sign(wrong_message.clone(), &staked_key)
+                ),
+            )
+            .assert_failed_with("Unauthorized");
+
+        let xrd_before = test_engine.current_balance("XRD");
+        call_admin_method(
+            &mut test_engine,
+            "slash_signer_for_wrong_price",
+            env_args!(
+                OracleKeyType::Bls12381,
+                staked_key.public_key().to_string(),
+                wrong_message.to_string(),
+                sign(wrong_message, &staked_key)
+            ),
+        )
+        .assert_is_success();
+
+        assert_eq!(test_engine.current_balance("XRD") - xrd_before, dec!(100));
+
+        let stake: SignerStake = test_engine
+            .get_non_fungible_data("Morpher signer stake", stake_ids.first().unwrap().clone());
+        assert_eq!(stake.amount, dec!(0));
+
+        // The slashed key no longer signs prices
+        let message = price_message(dec!(1), 0, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &staked_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_failed_with("[ORACLE-200]");
+    }
+
+    #[test]
+    fn test_slash_signer_needs_conflicting_prices() {
+        let (mut test_engine, _, staked_key) = instantiate_with_staked_signer();
+
+        // The same nonce signed again at a later time is not an equivocation
+        let message_a = price_message(dec!(1), 7, 0);
+        let message_b = price_message(dec!(2), 7, 60);
+
+        call_admin_method(
+            &mut test_engine,
+            "slash_signer",
+            env_args!(
+                OracleKeyType::Bls12381,
+                staked_key.public_key().to_string(),
+                message_a.to_string(),
+                sign(message_a, &staked_key),
+                message_b.to_string(),
+                sign(message_b, &staked_key)
+            ),
+        )
+        .assert_failed_with("[ORACLE-506]");
+    }
+
+    pub fn report_equivocation(
+        test_engine: &mut TestEngine,
+        key: &Bls12381G1PrivateKey,
+        nonce: u64,
+    ) -> TransactionReceipt {
+        let message_a = price_message(dec!(1), nonce, 0);
+        let message_b = price_message(dec!(2), nonce, 0);
+
+        test_engine.call_method(
+            "report_equivocation",
+            env_args!(
+                OracleKeyType::Bls12381,
+                key.public_key().to_string(),
+                message_a.to_string(),
+                sign(message_a, key),
+                message_b.to_string(),
+                sign(message_b, key)
+            ),
+        )
+    }
+
+    #[test]
+    fn test_report_equivocation() {
+        let (mut test_engine, _, staked_key) = instantiate_with_approved_signer();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        report_equivocation(&mut test_engine, &staked_key, 7).assert_is_success();
+
+        let report: Option<EquivocationReport> = test_engine
+            .call_method(
+                "get_equivocation_report",
+                env_args!(
+                    OracleKeyType::Bls12381,
+                    staked_key.public_key().to_string(),
+                    "GATEIO:XRD_USDT".to_string(),
+                    7u64
+                ),
+            )
+            .expect_commit_success()
+            .output(1);
+        let report = report.unwrap();
+        assert_eq!(report.price_a, dec!(1));
+        assert_eq!(report.price_b, dec!(2));
+
+        // The same offense is only reported once
+        report_equivocation(&mut test_engine, &staked_key, 7).assert_failed_with("[ORACLE-508]");
+
+        // Signers are not disabled by default
+        let message = price_message(dec!(1), 0, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &staked_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_is_success();
+    }
+
+    #[test]
+    fn test_report_equivocation_disables_signer() {
+        let (mut test_engine, secret_key, staked_key) = instantiate_with_approved_signer();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        call_admin_method(
+            &mut test_engine,
+            "set_disable_equivocating_signers",
+            env_args!(true),
+        )
+        .assert_is_success();
+
+        report_equivocation(&mut test_engine, &staked_key, 7).assert_is_success();
+
+        let message = price_message(dec!(1), 0, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message.clone(), &staked_key),
+                    NonFungible::Proof("Morpher subscription", ids.clone())
+                ),
+            )
+            .assert_failed_with("[ORACLE-200]");
+
+        // The last signer is only flagged and keeps signing
+        report_equivocation(&mut test_engine, &secret_key, 8).assert_is_success();
+
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_is_success();
+    }
+
+    #[test]
+    fn test_check_expired_price_message_fails() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        test_engine.advance_time(120);
+
+        let price_message = PriceMessage {
+            market_id: "GATEIO:XRD_USDT".to_string(),
+            price: dec!(1),
+            nonce: 0,
+            created_at: 0,
+            bid: dec!(1),
+            ask: dec!(1),
+            confidence: dec!(0),
+            valid_until: 60,
+        };
+
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    price_message.to_string(),
+                    sign(price_message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_failed_with("This price message has expired!");
+    }
+
+    #[test]
+    fn test_check_price_message_from_the_future_fails() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        let price_message = PriceMessage {
+            market_id: "GATEIO:XRD_USDT".to_string(),
+            price: dec!(1),
+            nonce: 0,
+            created_at: 3600,
+            bid: dec!(1),
+            ask: dec!(1),
+            confidence: dec!(0),
+            valid_until: 3660,
+        };
+
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    price_message.to_string(),
+                    sign(price_message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_failed_with("This price message was created in the future!");
+    }
+
+    #[test]
+    fn test_check_price_input_with_expired_subscription_fails() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        test_engine.advance_time(2_592_000 * 6 + 60);
+
+        let price_message = PriceMessage {
+            market_id: "GATEIO:XRD_USDT".to_string(),
+            price: dec!(1),
+            nonce: 0,
+            created_at: 2_592_000 * 6 + 60,
+            bid: dec!(1),
+            ask: dec!(1),
+            confidence: dec!(0),
+            valid_until: u64::MAX,
+        };
+
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    price_message.to_string(),
+                    sign(price_message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_failed_with("Subscription has expired!");
+    }
+
+    #[test]
+    fn test_check_price_input_with_royalty() {
+        let (mut test_engine, secret_key) = instantiate_oracle();
+
+        let price_message = PriceMessage {
+            market_id: "GATEIO:XRD_USDT".to_string(),
+            price: dec!(1),
+            nonce: 0,
+            created_at: 0,
+            bid: dec!(1),
+            ask: dec!(1),
+            confidence: dec!(0),
+            valid_until: u64::MAX,
+        };
+
+        let receipt = test_engine.call_method(
+            "check_price_input_with_royalty",
+            env_args!(price_message.to_string(), sign(price_message, &secret_key)),
+        );
+        receipt.assert_is_success();
+
+        assert_eq!(receipt.fee_summary.total_royalty_cost_in_xrd, dec!(1));
+    }
+
+    #[test]
+    fn test_suspend_and_reinstate_subscription() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+        let subscription_id = ids.first().unwrap().clone();
+
+        test_engine
+            .call_method(
+                "suspend_subscription",
+                env_args!(subscription_id.clone(), "Abuse".to_string()),
+            )
+            .assert_failed_with("Unauthorized");
+
+        call_admin_method(
+            &mut test_engine,
+            "suspend_subscription",
+            env_args!(subscription_id.clone(), "Abuse".to_string()),
+        )
+        .assert_is_success();
+        call_admin_method(
+            &mut test_engine,
+            "suspend_subscription",
+            env_args!(subscription_id.clone(), "Abuse".to_string()),
+        )
+        .assert_failed_with("[ORACLE-420]");
+
+        let data: OracleSubscription =
+            test_engine.get_non_fungible_data("Morpher subscription", subscription_id.clone());
+        assert!(data.suspended);
+        assert_eq!(data.suspension_reason, "Abuse");
+
+        let message = price_message(dec!(1), 0, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids.clone())
+                ),
+            )
+            .assert_failed_with("[ORACLE-420]");
+
+        // The backend keeps syncing the call count of suspended subscriptions
+        let request = format!(
+            "GATEIO:XRD_USDT##5##{}##{}",
+            secret_key.public_key(),
+            subscription_id
+        );
+        test_engine
+            .call_method(
+                "set_subscription_cur_nonce",
+                env_args!(
+                    subscription_id.clone(),
+                    request.clone(),
+                    secret_key.sign_v1(request.as_bytes()).to_string()
+                ),
+            )
+            .assert_is_success();
+
+        call_admin_method(
+            &mut test_engine,
+            "reinstate_subscription",
+            env_args!(subscription_id.clone()),
+        )
+        .assert_is_success();
+        call_admin_method(
+            &mut test_engine,
+            "reinstate_subscription",
+            env_args!(subscription_id.clone()),
+        )
+        .assert_failed_with("[ORACLE-421]");
+
+        let data: OracleSubscription =
+            test_engine.get_non_fungible_data("Morpher subscription", subscription_id);
+        assert!(!data.suspended);
+        assert!(data.suspension_reason.is_empty());
+        assert_eq!(data.cur_nonce, 5);
+
+        let message = price_message(dec!(1), 1, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_is_success();
+    }
+
+    #[test]
+    fn test_verify_price_input_does_not_consume_nonce() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        let price_message = PriceMessage {
+            market_id: "GATEIO:XRD_USDT".to_string(),
+            price: dec!(1),
+            nonce: 0,
+            created_at: 0,
+            bid: dec!(1),
+            ask: dec!(1),
+            confidence: dec!(0),
+            valid_until: u64::MAX,
+        };
+
+        for method in ["verify_price_input", "verify_price_input", "check_price_input"] {
+            test_engine
+                .call_method(
+                    method,
+                    env_args!(
+                        price_message.to_string(),
+                        sign(price_message.clone(), &secret_key),
+                        NonFungible::Proof("Morpher subscription", ids.clone())
+                    ),
+                )
+                .assert_is_success();
+        }
+    }
+
+    #[test]
+    fn test_renew_subscription() {
+        let (mut test_engine, _) = instantiate_with_subscription();
+
+        let subscription_id = test_engine.current_ids_balance("Morpher subscription");
+
+        test_engine
+            .call_method_builder(
+                "renew_subscription",
+                env_args!(
+                    subscription_id.clone().first().unwrap().clone(),
+                    1u64,
+                    Fungible::Bucket("XRD", 30)
+                ),
+            )
+            .output("rtm/", "renew_subscription")
+            .execute()
+            .assert_is_success();
+
+        let data: OracleSubscription = test_engine.get_non_fungible_data(
+            "Morpher subscription",
+            subscription_id.first().unwrap().clone(),
+        );
+
+        assert_eq!(data.expiration_time, 7 * 2_592_000);
+        assert_eq!(data.expiry_date, "1970-07-30 00:00 UTC");
+    }
+
+    #[test]
+    fn test_renew_subscription_after_expiry() {
+        let (mut test_engine, _) = instantiate_with_subscription();
+
+        let subscription_id = test_engine.current_ids_balance("Morpher subscription");
+
+        test_engine.advance_time(7 * 2_592_000);
+
+        test_engine
+            .call_method(
+                "renew_subscription",
+                env_args!(
+                    subscription_id.clone().first().unwrap().clone(),
+                    1u64,
+                    Fungible::Bucket("XRD", 30)
+                ),
+            )
+            .assert_is_success();
+
+        let data: OracleSubscription = test_engine.get_non_fungible_data(
+            "Morpher subscription",
+            subscription_id.first().unwrap().clone(),
+        );
+
+        assert_eq!(data.expiration_time, 8 * 2_592_000);
+    }
+
+    #[test]
+    fn test_expired_subscription_renews_from_credit() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        test_engine
+            .call_method(
+                "top_up_subscription_credit",
+                env_args!(ids.first().unwrap().clone(), Fungible::Bucket("XRD", 40)),
+            )
+            .assert_is_success();
+
+        test_engine.advance_time(2_592_000 * 6 + 60);
+
+        let price_message = PriceMessage {
+            market_id: "GATEIO:XRD_USDT".to_string(),
+            price: dec!(1),
+            nonce: 0,
+            created_at: 2_592_000 * 6 + 60,
+            bid: dec!(1),
+            ask: dec!(1),
+            confidence: dec!(0),
+            valid_until: u64::MAX,
+        };
+
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    price_message.to_string(),
+                    sign(price_message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids.clone())
+                ),
+            )
+            .assert_is_success();
+
+        let data: OracleSubscription = test_engine
+            .get_non_fungible_data("Morpher subscription", ids.first().unwrap().clone());
+
+        // One month was paid out of the 40 XRD of credit
+        assert_eq!(data.expiration_time, 2_592_000 * 7 + 60);
+        assert_eq!(data.max_nonce, 7_000_000);
+    }
+
+    pub fn instantiate_with_credited_subscriptions() -> TestEngine {
+        let (mut test_engine, _) = instantiate_with_subscription();
+
+        // A second subscription #2# for a single month
+        test_engine
+            .call_method(
+                "new_subscription",
+                env_args!(1u64, Fungible::Bucket("XRD", 30)),
+            )
+            .assert_is_success();
+
+        test_engine
+            .call_method(
+                "top_up_subscription_credit",
+                env_args!(NonFungibleLocalId::integer(1), Fungible::Bucket("XRD", 40)),
+            )
+            .assert_is_success();
+        test_engine
+            .call_method(
+                "top_up_subscription_credit",
+                env_args!(NonFungibleLocalId::integer(2), Fungible::Bucket("XRD", 10)),
+            )
+            .assert_is_success();
+
+        test_engine
+    }
+
+    #[test]
+    fn test_process_renewals() {
+        let mut test_engine = instantiate_with_credited_subscriptions();
+
+        test_engine.advance_time(2_592_000 * 6 + 60);
+
+        let renewed: Vec<NonFungibleLocalId> = test_engine
+            .call_method(
+                "process_renewals",
+                env_args!(vec![NonFungibleLocalId::integer(1)]),
+            )
+            .expect_commit_success()
+            .output(1);
+        assert_eq!(renewed, vec![NonFungibleLocalId::integer(1)]);
+
+        let data: OracleSubscription = test_engine
+            .get_non_fungible_data("Morpher subscription", NonFungibleLocalId::integer(1));
+        assert_eq!(data.expiration_time, 2_592_000 * 7 + 60);
+
+        let credit: Decimal = test_engine
+            .call_method(
+                "get_subscription_credit",
+                env_args!(NonFungibleLocalId::integer(1)),
+            )
+            .expect_commit_success()
+            .output(1);
+        assert_eq!(credit, dec!(10));
+    }
+
+    #[test]
+    fn test_process_renewals_skips_low_credit() {
+        let mut test_engine = instantiate_with_credited_subscriptions();
+
+        // #1# is not expired yet, #2# expired but only has 10 XRD of credit
+        test_engine.advance_time(2_592_000 + 60);
+
+        let renewed: Vec<NonFungibleLocalId> = test_engine
+            .call_method(
+                "process_renewals",
+                env_args!(vec![
+                    NonFungibleLocalId::integer(1),
+                    NonFungibleLocalId::integer(2)
+                ]),
+            )
+            .expect_commit_success()
+            .output(1);
+        assert!(renewed.is_empty());
+
+        let data: OracleSubscription = test_engine
+            .get_non_fungible_data("Morpher subscription", NonFungibleLocalId::integer(2));
+        assert_eq!(data.expiration_time, 2_592_000);
+
+        let credit: Decimal = test_engine
+            .call_method(
+                "get_subscription_credit",
+                env_args!(NonFungibleLocalId::integer(2)),
+            )
+            .expect_commit_success()
+            .output(1);
+        assert_eq!(credit, dec!(10));
+    }
+
+    #[test]
+    fn test_withdraw_subscription_credit() {
+        let mut test_engine = instantiate_with_credited_subscriptions();
+        let ids = vec![NonFungibleLocalId::integer(1)];
+
+        test_engine
+            .call_method(
+                "withdraw_subscription_credit",
+                env_args!(
+                    NonFungible::Proof("Morpher subscription", ids.clone()),
+                    dec!(41)
+                ),
+            )
+            .assert_failed_with("[ORACLE-426]");
+
+        let xrd_before = test_engine.current_balance("XRD");
+        test_engine
+            .call_method(
+                "withdraw_subscription_credit",
+                env_args!(NonFungible::Proof("Morpher subscription", ids), dec!(15)),
+            )
+            .assert_is_success();
+        assert_eq!(test_engine.current_balance("XRD") - xrd_before, dec!(15));
+
+        let credit: Decimal = test_engine
+            .call_method(
+                "get_subscription_credit",
+                env_args!(NonFungibleLocalId::integer(1)),
+            )
+            .expect_commit_success()
+            .output(1);
+        assert_eq!(credit, dec!(25));
+    }
+
+    #[test]
+    fn test_check_price_input_with_delegate() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        test_engine
+            .call_method(
+                "mint_subscription_delegate",
+                env_args!(
+                    NonFungible::Proof("Morpher subscription", ids.clone()),
+                    1000u64,
+                    2_592_000u64
+                ),
+            )
+            .assert_is_success();
+
+        let delegate_ids = test_engine.current_ids_balance("Morpher subscription delegate");
+
+        let price_message = PriceMessage {
+            market_id: "GATEIO:XRD_USDT".to_string(),
+            price: dec!(1),
+            nonce: 0,
+            created_at: 0,
+            bid: dec!(1),
+            ask: dec!(1),
+            confidence: dec!(0),
+            valid_until: u64::MAX,
+        };
+
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    price_message.to_string(),
+                    sign(price_message, &secret_key),
+                    NonFungible::Proof("Morpher subscription delegate", delegate_ids.clone())
+                ),
+            )
+            .assert_is_success();
+
+        let data: OracleSubscription = test_engine
+            .get_non_fungible_data("Morpher subscription", ids.first().unwrap().clone());
+        let delegate: SubscriptionDelegate = test_engine.get_non_fungible_data(
+            "Morpher subscription delegate",
+            delegate_ids.first().unwrap().clone(),
+        );
+
+        assert_eq!(data.max_nonce, 6_000_000 - 1000);
+        assert_eq!(data.cur_nonce, 0);
+        assert_eq!(delegate.calls_used, 1);
+    }
+
+    #[test]
+    fn test_revoked_delegate_is_rejected() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        test_engine
+            .call_method(
+                "mint_subscription_delegate",
+                env_args!(
+                    NonFungible::Proof("Morpher subscription", ids.clone()),
+                    1000u64,
+                    2_592_000u64
+                ),
+            )
+            .assert_is_success();
+        let delegate_ids = test_engine.current_ids_balance("Morpher subscription delegate");
+
+        let message = price_message(dec!(1), 0, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    NonFungible::Proof("Morpher subscription delegate", delegate_ids.clone())
+                ),
+            )
+            .assert_is_success();
+
+        let receipt = test_engine.call_method(
+            "revoke_subscription_delegate",
+            env_args!(
+                NonFungible::Proof("Morpher subscription", ids.clone()),
+                delegate_ids.first().unwrap().clone()
+            ),
+        );
+        let revoked = receipt
+            .expect_commit_success()
+            .application_events
+            .iter()
+            .find_map(|(_, event)| scrypto_decode::<SubscriptionDelegateRevoked>(event).ok())
+            .unwrap();
+        assert_eq!(revoked.returned_calls, 999);
+
+        // The unused calls of the delegate go back to the subscription
+        let data: OracleSubscription =
+            test_engine.get_non_fungible_data("Morpher subscription", ids.first().unwrap().clone());
+        assert_eq!(data.max_nonce, 6_000_000 - 1);
+
+        let delegate: SubscriptionDelegate = test_engine.get_non_fungible_data(
+            "Morpher subscription delegate",
+            delegate_ids.first().unwrap().clone(),
+        );
+        assert!(delegate.revoked);
+        assert_eq!(delegate.calls_used, 1);
+
+        let message = price_message(dec!(1), 1, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    NonFungible::Proof("Morpher subscription delegate", delegate_ids.clone())
+                ),
+            )
+            .assert_failed_with("[ORACLE-417]");
+
+        // The calls are only given back once
+        test_engine
+            .call_method(
+                "revoke_subscription_delegate",
+                env_args!(
+                    NonFungible::Proof("Morpher subscription", ids),
+                    delegate_ids.first().unwrap().clone()
+                ),
+            )
+            .assert_failed_with("[ORACLE-417]");
+    }
+
+    #[test]
+    fn test_delegate_shares_subscription_rate_limit() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        test_engine
+            .call_method(
+                "set_subscription_rate_limit",
+                env_args!(
+                    NonFungible::Proof("Morpher subscription", ids.clone()),
+                    Some((1u64, 3600u64))
+                ),
+            )
+            .assert_is_success();
+        test_engine
+            .call_method(
+                "mint_subscription_delegate",
+                env_args!(
+                    NonFungible::Proof("Morpher subscription", ids.clone()),
+                    1000u64,
+                    2_592_000u64
+                ),
+            )
+            .assert_is_success();
+        let delegate_ids = test_engine.current_ids_balance("Morpher subscription delegate");
+
+        let message = price_message(dec!(1), 0, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    NonFungible::Proof("Morpher subscription", ids)
+                ),
+            )
+            .assert_is_success();
+
+        let message = price_message(dec!(1), 1, 0);
+        test_engine
+            .call_method(
+                "check_price_input",
+                env_args!(
+                    message.to_string(),
+                    sign(message, &secret_key),
+                    NonFungible::Proof("Morpher subscription delegate", delegate_ids)
+                ),
+            )
+            .assert_failed_with("[ORACLE-408]");
+    }
+
+    #[test]
+    fn test_expired_subscription_cannot_mint_delegate() {
+        let (mut test_engine, _) = instantiate_with_subscription();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
 
-        let secret_key = Bls12381G1PrivateKey::from_u64(1).unwrap();
+        test_engine.advance_time(2_592_000 * 6 + 60);
 
-        test_engine.add_global_package("morpher package", &TRADING);
+        test_engine
+            .call_method(
+                "mint_subscription_delegate",
+                env_args!(
+                    NonFungible::Proof("Morpher subscription", ids),
+                    1000u64,
+                    0u64
+                ),
+            )
+            .assert_failed_with("[ORACLE-403]");
+    }
 
-        test_engine.new_component(
-            "morpher oracle",
-            "MorpherOracle",
-            "instantiate",
+    pub fn set_migration_key(test_engine: &mut TestEngine) -> Bls12381G1PrivateKey {
+        let migration_key = Bls12381G1PrivateKey::from_u64(4).unwrap();
+        call_admin_method(
+            test_engine,
+            "set_migration_pub_key",
             env_args!(
-                secret_key.public_key().to_string(),
-                dec!("30"),
-                Environment::Account("default")
+                Some((
+                    OracleKeyType::Bls12381,
+                    migration_key.public_key().to_string()
+                )),
+                3600u64
             ),
-        );
+        )
+        .assert_is_success();
 
-        test_engine.call_faucet();
+        migration_key
+    }
 
-        (test_engine, secret_key)
+    pub fn export_subscription(
+        test_engine: &mut TestEngine,
+        target: Environment,
+        recipient: Environment,
+    ) -> String {
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+
+        test_engine
+            .call_method(
+                "export_subscription",
+                env_args!(
+                    NonFungible::Bucket("Morpher subscription", ids),
+                    target,
+                    recipient
+                ),
+            )
+            .expect_commit_success()
+            .application_events
+            .iter()
+            .find_map(|(_, event)| scrypto_decode::<OracleSubscriptionExported>(event).ok())
+            .unwrap()
+            .receipt
     }
 
     #[test]
-    fn test_new_subscription() {
-        let (mut test_engine, _) = instantiate_oracle();
-
-        let xrd_before = test_engine.current_balance("XRD");
+    fn test_export_and_import_subscription() {
+        let (mut test_engine, secret_key) = instantiate_with_subscription();
+        let migration_key = set_migration_key(&mut test_engine);
+        let ids = test_engine.current_ids_balance("Morpher subscription");
 
         test_engine
-            .call_method_builder(
-                "new_subscription",
-                env_args!(6u64, Fungible::Bucket("XRD", 190)),
+            .call_method(
+                "set_subscription_rate_limit",
+                env_args!(
+                    NonFungible::Proof("Morpher subscription", ids.clone()),
+                    Some((10u64, 3600u64))
+                ),
             )
-            .output("rtm/", "new_subscription")
-            .execute()
             .assert_is_success();
+        test_engine
+            .call_method(
+                "mint_subscription_delegate",
+                env_args!(
+                    NonFungible::Proof("Morpher subscription", ids.clone()),
+                    1000u64,
+                    2_592_000u64
+                ),
+            )
+            .assert_is_success();
+        let delegate_ids = test_engine.current_ids_balance("Morpher subscription delegate");
 
-        let xrd_after = test_engine.current_balance("XRD");
-
-        // Check that only 180 XRD have been paid for 6 months
-        assert_eq!(xrd_before - xrd_after, dec!(180));
-
-        // Check that we received a subscription nft
-        let ids = test_engine.current_ids_balance("morpher subscription");
-
-        assert_eq!(ids.len(), 1);
+        // A subscription with active delegates can't be exported
+        test_engine
+            .call_method(
+                "export_subscription",
+                env_args!(
+                    NonFungible::Bucket("Morpher subscription", ids.clone()),
+                    Environment::Component("morpher oracle"),
+                    Environment::Account("default")
+                ),
+            )
+            .assert_failed_with("[ORACLE-428]");
 
-        // Check that it has the right expiration time
-        let data: OracleSubscription =
-            test_engine.get_non_fungible_data("Morpher subscription", ids.first().unwrap().clone());
+        test_engine
+            .call_method(
+                "revoke_subscription_delegate",
+                env_args!(
+                    NonFungible::Proof("Morpher subscription", ids.clone()),
+                    delegate_ids.first().unwrap().clone()
+                ),
+            )
+            .assert_is_success();
+        let exported: OracleSubscription = test_engine
+            .get_non_fungible_data("Morpher subscription", ids.first().unwrap().clone());
 
-        assert_eq!(data.expiration_time, 6 * 2_592_000);
-    }
+        let receipt = export_subscription(
+            &mut test_engine,
+            Environment::Component("morpher oracle"),
+            Environment::Account("default"),
+        );
+        assert!(receipt.starts_with("migration-receipt-v1:"));
+        assert!(test_engine
+            .current_ids_balance("Morpher subscription")
+            .is_empty());
 
-    #[test]
-    fn test_new_zero_month_subscription_fails() {
-        let (mut test_engine, _) = instantiate_oracle();
+        // Only the migration key signs receipts
+        let signature = secret_key.sign_v1(receipt.as_bytes()).to_string();
+        test_engine
+            .call_method(
+                "import_subscription",
+                env_args!(receipt.clone(), signature, Environment::Account("default")),
+            )
+            .assert_failed_with("[ORACLE-200]");
 
+        // The time spent in transit is not given back
+        test_engine.advance_time(600);
+        let signature = migration_key.sign_v1(receipt.as_bytes()).to_string();
         test_engine
             .call_method(
-                "new_subscription",
-                env_args!(0u64, Fungible::Bucket("XRD", 190)),
+                "import_subscription",
+                env_args!(
+                    receipt.clone(),
+                    signature.clone(),
+                    Environment::Account("default")
+                ),
             )
-            .assert_failed_with("Cannot add 0 months to the subscription!");
-    }
+            .assert_is_success();
 
-    #[test]
-    fn test_new_subscription_with_other_token_fails() {
-        let (mut test_engine, _) = instantiate_oracle();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+        assert_eq!(ids.first().unwrap(), &NonFungibleLocalId::integer(2));
+
+        let imported: OracleSubscription = test_engine
+            .get_non_fungible_data("Morpher subscription", ids.first().unwrap().clone());
+        assert_eq!(imported.expiration_time, exported.expiration_time);
+        assert_eq!(imported.max_nonce, 6_000_000);
+        assert!(!imported.trial);
+        assert!(imported.billing_mode == BillingMode::Monthly);
+        let rate_limit = imported.rate_limit.unwrap();
+        assert_eq!((rate_limit.max_calls, rate_limit.window), (10, 3600));
 
-        test_engine.new_token("test token", 1000);
         test_engine
             .call_method(
-                "new_subscription",
-                env_args!(6u64, Fungible::Bucket("test token", 190)),
+                "import_subscription",
+                env_args!(receipt, signature, Environment::Account("default")),
             )
-            .assert_failed_with("The payment should be made in XRD tokens!");
+            .assert_failed_with("[ORACLE-419]");
     }
 
     #[test]
-    fn test_new_subscription_with_not_enough_tokens_fails() {
-        let (mut test_engine, _) = instantiate_oracle();
+    fn test_import_subscription_for_another_oracle_fails() {
+        let (mut test_engine, _) = instantiate_with_subscription();
+        let migration_key = set_migration_key(&mut test_engine);
+
+        let receipt = export_subscription(
+            &mut test_engine,
+            Environment::Account("default"),
+            Environment::Account("default"),
+        );
 
+        let signature = migration_key.sign_v1(receipt.as_bytes()).to_string();
         test_engine
             .call_method(
-                "new_subscription",
-                env_args!(6u64, Fungible::Bucket("XRD", 170)),
+                "import_subscription",
+                env_args!(receipt, signature, Environment::Account("default")),
             )
-            .assert_failed_with(
-                "For 6 months, the expected payment is 180 XRD (only supplied 170)",
-            );
+            .assert_failed_with("[ORACLE-422]");
     }
 
-    pub fn instantiate_with_subscription() -> (TestEngine, Bls12381G1PrivateKey) {
-        let (mut test_engine, secret_key) = instantiate_oracle();
+    #[test]
+    fn test_import_subscription_for_another_recipient_fails() {
+        let (mut test_engine, _) = instantiate_with_subscription();
+        let migration_key = set_migration_key(&mut test_engine);
+
+        let receipt = export_subscription(
+            &mut test_engine,
+            Environment::Component("morpher oracle"),
+            Environment::Component("morpher oracle"),
+        );
 
+        let signature = migration_key.sign_v1(receipt.as_bytes()).to_string();
         test_engine
-            .call_method_builder(
-                "new_subscription",
-                env_args!(6u64, Fungible::Bucket("XRD", 190)),
+            .call_method(
+                "import_subscription",
+                env_args!(receipt, signature, Environment::Account("default")),
             )
-            .output("rtm/", "new_subscription")
-            .execute()
-            .assert_is_success();
-
-        (test_engine, secret_key)
+            .assert_failed_with("[ORACLE-429]");
     }
 
     #[test]
-    fn test_renew_subscription() {
+    fn test_import_subscription_needs_fresh_receipt() {
         let (mut test_engine, _) = instantiate_with_subscription();
 
-        let subscription_id = test_engine.current_ids_balance("Morpher subscription");
+        let receipt = export_subscription(
+            &mut test_engine,
+            Environment::Component("morpher oracle"),
+            Environment::Account("default"),
+        );
 
+        // Nothing can be imported until the admin sets a migration key
+        let migration_key = Bls12381G1PrivateKey::from_u64(4).unwrap();
+        let signature = migration_key.sign_v1(receipt.as_bytes()).to_string();
         test_engine
-            .call_method_builder(
-                "renew_subscription",
+            .call_method(
+                "import_subscription",
                 env_args!(
-                    subscription_id.clone().first().unwrap().clone(),
-                    1u64,
-                    Fungible::Bucket("XRD", 30)
+                    receipt.clone(),
+                    signature.clone(),
+                    Environment::Account("default")
                 ),
             )
-            .output("rtm/", "renew_subscription")
-            .execute()
-            .assert_is_success();
+            .assert_failed_with("[ORACLE-206]");
 
-        let data: OracleSubscription = test_engine.get_non_fungible_data(
-            "Morpher subscription",
-            subscription_id.first().unwrap().clone(),
-        );
+        set_migration_key(&mut test_engine);
+        test_engine.advance_time(3601);
+        test_engine
+            .call_method(
+                "import_subscription",
+                env_args!(receipt, signature, Environment::Account("default")),
+            )
+            .assert_failed_with("[ORACLE-427]");
+    }
 
-        assert_eq!(data.expiration_time, 7 * 2_592_000);
+    #[test]
+    fn test_staked_key_cannot_be_migration_key() {
+        let (mut test_engine, _, staked_key) = instantiate_with_staked_signer();
+
+        call_admin_method(
+            &mut test_engine,
+            "set_migration_pub_key",
+            env_args!(
+                Some((OracleKeyType::Bls12381, staked_key.public_key().to_string())),
+                3600u64
+            ),
+        )
+        .assert_failed_with("[ORACLE-207]");
     }
 
     #[test]
-    fn test_renew_subscription_after_expiry() {
-        let (mut test_engine, _) = instantiate_with_subscription();
+    fn test_grant_complimentary_subscription() {
+        let (mut test_engine, _) = instantiate_oracle();
 
-        let subscription_id = test_engine.current_ids_balance("Morpher subscription");
+        test_engine
+            .call_method(
+                "grant_complimentary_subscription",
+                env_args!(2_592_000u64, 1000u64),
+            )
+            .assert_failed_with("Unauthorized");
 
-        test_engine.advance_time(7 * 2_592_000);
+        let receipt = call_admin_method(
+            &mut test_engine,
+            "grant_complimentary_subscription",
+            env_args!(2_592_000u64, 1000u64),
+        );
+        let update = receipt
+            .expect_commit_success()
+            .application_events
+            .iter()
+            .find_map(|(_, event)| scrypto_decode::<OracleSubscriptionUpdate>(event).ok())
+            .unwrap();
+        assert!(update.complimentary);
+
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+        let subscription_id = ids.first().unwrap().clone();
+        let data: OracleSubscription =
+            test_engine.get_non_fungible_data("Morpher subscription", subscription_id.clone());
+        assert!(data.complimentary);
+        assert_eq!(data.tier, "Complimentary");
+        assert_eq!(data.expiration_time, 2_592_000);
+        assert_eq!(data.max_nonce, 1000);
 
+        // A paid renewal turns it into an ordinary subscription
         test_engine
             .call_method(
                 "renew_subscription",
-                env_args!(
-                    subscription_id.clone().first().unwrap().clone(),
-                    1u64,
-                    Fungible::Bucket("XRD", 30)
-                ),
+                env_args!(subscription_id.clone(), 1u64, Fungible::Bucket("XRD", 30)),
             )
             .assert_is_success();
 
-        let data: OracleSubscription = test_engine.get_non_fungible_data(
-            "Morpher subscription",
-            subscription_id.first().unwrap().clone(),
+        let data: OracleSubscription =
+            test_engine.get_non_fungible_data("Morpher subscription", subscription_id);
+        assert!(!data.complimentary);
+        assert_eq!(data.tier, "Monthly");
+
+        test_engine.advance_time(60);
+        call_admin_method(
+            &mut test_engine,
+            "grant_complimentary_subscription",
+            env_args!(u64::MAX, 1000u64),
+        )
+        .assert_failed_with("[ORACLE-425]");
+    }
+
+    #[test]
+    fn test_imported_complimentary_subscription_stays_complimentary() {
+        let (mut test_engine, _) = instantiate_oracle();
+        let migration_key = set_migration_key(&mut test_engine);
+
+        call_admin_method(
+            &mut test_engine,
+            "grant_complimentary_subscription",
+            env_args!(2_592_000u64, 1000u64),
+        )
+        .assert_is_success();
+
+        let receipt = export_subscription(
+            &mut test_engine,
+            Environment::Component("morpher oracle"),
+            Environment::Account("default"),
         );
+        let signature = migration_key.sign_v1(receipt.as_bytes()).to_string();
+        test_engine
+            .call_method(
+                "import_subscription",
+                env_args!(receipt, signature, Environment::Account("default")),
+            )
+            .assert_is_success();
 
-        assert_eq!(data.expiration_time, 8 * 2_592_000);
+        let ids = test_engine.current_ids_balance("Morpher subscription");
+        let data: OracleSubscription =
+            test_engine.get_non_fungible_data("Morpher subscription", ids.first().unwrap().clone());
+        assert!(data.complimentary);
+        assert_eq!(data.tier, "Complimentary");
+        assert_eq!(data.max_nonce, 1000);
     }
 
-    pub fn instantiate_with_trading() -> (TestEngine, Bls12381G1PrivateKey) {
+    pub fn instantiate_with_gumball_machine() -> (TestEngine, Bls12381G1PrivateKey) {
         let (mut test_engine, secret_key) = instantiate_with_subscription();
 
         let ids = test_engine.current_ids_balance("Morpher subscription");
         test_engine.new_component(
-            "trading component",
-            "TradingComponent",
-            "instantiate",
+            "gumball machine",
+            "GumballMachine",
+            "instantiate_gumball_machine",
             env_args!(
-                30i64,
-                Environment::Component("Morpher Oracle"),
+                30u64,
+                Environment::Component("morpher oracle"),
                 NonFungible::Bucket("Morpher subscription", ids),
                 Environment::Account("default")
             ),
         );
 
-        test_engine.set_current_component("trading component");
+        test_engine.set_current_component("gumball machine");
 
         (test_engine, secret_key)
     }
 
     #[test]
     fn test_buy() {
-        let (mut test_engine, secret_key) = instantiate_with_trading();
+        let (mut test_engine, secret_key) = instantiate_with_gumball_machine();
 
         let price_message = PriceMessage {
-            market_id: 0,
+            market_id: "GATEIO:XRD_USDT".to_string(),
             price: dec!(1),
             nonce: 0,
             created_at: 0,
+            bid: dec!(1),
+            ask: dec!(1),
+            confidence: dec!(0),
+            valid_until: u64::MAX,
         };
 
+        let xrd_before = test_engine.current_balance("XRD");
+
         test_engine
             .call_method_builder(
-                "buy",
+                "buy_gumball",
                 env_args!(
-                    dec!(3),
                     Fungible::Bucket("XRD", 3),
                     price_message.to_string(),
                     sign(price_message, &secret_key)
@@ -222,25 +2551,30 @@ mod trading_test {
             .execute()
             .assert_is_success();
 
-        assert_eq!(test_engine.current_balance("MPH"), dec!(3))
+        // A gumball costs 1 XRD at a price of 1, the change is returned
+        assert_eq!(test_engine.current_balance("GUM"), dec!(1));
+        assert_eq!(xrd_before - test_engine.current_balance("XRD"), dec!(1));
     }
 
     #[test]
     fn test_buy_with_expired_subscription_fails() {
-        let (mut test_engine, secret_key) = instantiate_with_trading();
-        test_engine.advance_time(2_592_000 * 6 + 1);
+        let (mut test_engine, secret_key) = instantiate_with_gumball_machine();
+        test_engine.advance_time(2_592_000 * 6 + 60);
         let price_message = PriceMessage {
-            market_id: 0,
+            market_id: "GATEIO:XRD_USDT".to_string(),
             price: dec!(1),
             nonce: 0,
             created_at: 0,
+            bid: dec!(1),
+            ask: dec!(1),
+            confidence: dec!(0),
+            valid_until: u64::MAX,
         };
 
         test_engine
             .call_method(
-                "buy",
+                "buy_gumball",
                 env_args!(
-                    dec!(3),
                     Fungible::Bucket("XRD", 3),
                     price_message.to_string(),
                     sign(price_message, &secret_key)
@@ -251,22 +2585,25 @@ mod trading_test {
 
     #[test]
     fn test_buy_fails_wrong_signature() {
-        let (mut test_engine, _) = instantiate_with_trading();
+        let (mut test_engine, _) = instantiate_with_gumball_machine();
 
         let secret_key = Bls12381G1PrivateKey::from_u64(2).unwrap();
 
         let price_message = PriceMessage {
-            market_id: 0,
+            market_id: "GATEIO:XRD_USDT".to_string(),
             price: dec!(1),
             nonce: 0,
             created_at: 0,
+            bid: dec!(1),
+            ask: dec!(1),
+            confidence: dec!(0),
+            valid_until: u64::MAX,
         };
 
         test_engine
             .call_method(
-                "buy",
+                "buy_gumball",
                 env_args!(
-                    dec!(3),
                     Fungible::Bucket("XRD", 3),
                     price_message.to_string(),
                     sign(price_message, &secret_key)
@@ -277,22 +2614,26 @@ mod trading_test {
 
     #[test]
     fn test_buy_fails_price_out_of_date() {
-        let (mut test_engine, secret_key) = instantiate_with_trading();
+        let (mut test_engine, secret_key) = instantiate_with_gumball_machine();
 
-        test_engine.advance_time(32);
+        // The clock of the oracle has minute precision
+        test_engine.advance_time(60);
 
         let price_message = PriceMessage {
-            market_id: 0,
+            market_id: "GATEIO:XRD_USDT".to_string(),
             price: dec!(1),
             nonce: 0,
             created_at: 0,
+            bid: dec!(1),
+            ask: dec!(1),
+            confidence: dec!(0),
+            valid_until: u64::MAX,
         };
 
         test_engine
             .call_method(
-                "buy",
+                "buy_gumball",
                 env_args!(
-                    dec!(3),
                     Fungible::Bucket("XRD", 3),
                     price_message.to_string(),
                     sign(price_message, &secret_key)
@@ -303,19 +2644,22 @@ mod trading_test {
 
     #[test]
     fn test_buy_already_used_nonce_fails() {
-        let (mut test_engine, secret_key) = instantiate_with_trading();
+        let (mut test_engine, secret_key) = instantiate_with_gumball_machine();
         let price_message = PriceMessage {
-            market_id: 0,
+            market_id: "GATEIO:XRD_USDT".to_string(),
             price: dec!(1),
             nonce: 0,
             created_at: 0,
+            bid: dec!(1),
+            ask: dec!(1),
+            confidence: dec!(0),
+            valid_until: u64::MAX,
         };
 
         test_engine
             .call_method(
-                "buy",
+                "buy_gumball",
                 env_args!(
-                    dec!(3),
                     Fungible::Bucket("XRD", 3),
                     price_message.to_string(),
                     sign(price_message.clone(), &secret_key)
@@ -325,9 +2669,8 @@ mod trading_test {
 
         test_engine
             .call_method(
-                "buy",
+                "buy_gumball",
                 env_args!(
-                    dec!(3),
                     Fungible::Bucket("XRD", 3),
                     price_message.to_string(),
                     sign(price_message, &secret_key)
@@ -337,53 +2680,215 @@ mod trading_test {
     }
 
     #[test]
-    fn test_sell() {
-        let (mut test_engine, secret_key) = instantiate_with_trading();
+    fn test_withdraw_earnings() {
+        let (mut test_engine, secret_key) = instantiate_with_gumball_machine();
         let price_message = PriceMessage {
-            market_id: 0,
+            market_id: "GATEIO:XRD_USDT".to_string(),
             price: dec!(1),
             nonce: 0,
             created_at: 0,
+            bid: dec!(1),
+            ask: dec!(1),
+            confidence: dec!(0),
+            valid_until: u64::MAX,
         };
 
         test_engine
-            .call_method_builder(
-                "buy",
+            .call_method(
+                "buy_gumball",
                 env_args!(
-                    dec!(3),
                     Fungible::Bucket("XRD", 3),
                     price_message.to_string(),
                     sign(price_message, &secret_key)
                 ),
             )
-            .output("rtm/", "sell")
+            .assert_is_success();
+
+        let xrd_before = test_engine.current_balance("xrd");
+
+        test_engine
+            .call_method_builder("withdraw_earnings", env_args!())
+            .with_badge("Gumball Machine Owner Badge")
             .execute()
             .assert_is_success();
 
-        let new_price_message = PriceMessage {
-            market_id: 0,
-            price: dec!(1),
-            nonce: 1,
-            created_at: 0,
-        };
+        let xrd_after = test_engine.current_balance("xrd");
 
-        let xrd_before = test_engine.current_balance("xrd");
+        assert_eq!(xrd_after - xrd_before, dec!(1));
+    }
+
+    #[test]
+    fn test_owner_badge_sets_and_locks_gumball_metadata() {
+        let (mut test_engine, _) = instantiate_with_gumball_machine();
+
+        update_metadata(
+            &mut test_engine,
+            "gumball machine",
+            "Gumball Machine Owner Badge",
+            |builder, gumball_machine| {
+                builder.set_metadata(gumball_machine, "name", "Renamed Gumballs".to_string())
+            },
+        )
+        .assert_is_success();
+
+        update_metadata(
+            &mut test_engine,
+            "gumball machine",
+            "Gumball Machine Owner Badge",
+            |builder, gumball_machine| builder.lock_metadata(gumball_machine, "name"),
+        )
+        .assert_is_success();
+
+        update_metadata(
+            &mut test_engine,
+            "gumball machine",
+            "Gumball Machine Owner Badge",
+            |builder, gumball_machine| {
+                builder.set_metadata(gumball_machine, "name", "Gumball Component".to_string())
+            },
+        )
+        .assert_failed_with("Locked");
+    }
+
+    pub fn request_gumball_price(test_engine: &mut TestEngine, market_id: &str) {
+        test_engine
+            .call_method_builder("request_price", env_args!(market_id.to_string()))
+            .with_badge("Gumball Machine Owner Badge")
+            .execute()
+            .assert_is_success();
+    }
+
+    pub fn fulfil(
+        test_engine: &mut TestEngine,
+        request_id: u64,
+        price_message: PriceMessage,
+        secret_key: &Bls12381G1PrivateKey,
+    ) -> TransactionReceipt {
+        test_engine.set_current_component("morpher oracle");
+        let receipt = test_engine
+            .call_method_builder(
+                "fulfil",
+                env_args!(
+                    request_id,
+                    price_message.to_string(),
+                    sign(price_message, secret_key)
+                ),
+            )
+            .with_badge("Oracle admin badge")
+            .execute();
+        test_engine.set_current_component("gumball machine");
 
+        receipt
+    }
+
+    #[test]
+    fn test_request_and_fulfil_price() {
+        let (mut test_engine, secret_key) = instantiate_with_gumball_machine();
+
+        request_gumball_price(&mut test_engine, "GATEIO:XRD_USDT");
+
+        fulfil(
+            &mut test_engine,
+            0,
+            price_message(dec!("1.5"), 0, 0),
+            &secret_key,
+        )
+        .expect_commit_success();
+
+        let status: Status = test_engine
+            .call_method("get_status", env_args!())
+            .expect_commit_success()
+            .output(1);
+        assert_eq!(status.requested_price, Some(dec!("1.5")));
+
+        // A request is only fulfilled once
+        fulfil(
+            &mut test_engine,
+            0,
+            price_message(dec!("1.5"), 1, 0),
+            &secret_key,
+        )
+        .assert_failed_with("[ORACLE-308]");
+    }
+
+    #[test]
+    fn test_fulfil_other_market_fails() {
+        let (mut test_engine, secret_key) = instantiate_with_gumball_machine();
+
+        request_gumball_price(&mut test_engine, "GATEIO:BTC_USDT");
+
+        fulfil(
+            &mut test_engine,
+            0,
+            price_message(dec!(1), 0, 0),
+            &secret_key,
+        )
+        .assert_failed_with("[ORACLE-309]");
+    }
+
+    #[test]
+    fn test_request_price_needs_a_subscription() {
+        let (mut test_engine, _) = instantiate_with_gumball_machine();
+
+        test_engine.advance_time(2_592_000 * 6 + 60);
+
+        test_engine
+            .call_method_builder("request_price", env_args!("GATEIO:XRD_USDT".to_string()))
+            .with_badge("Gumball Machine Owner Badge")
+            .execute()
+            .assert_failed_with("Subscription has expired!");
+    }
+
+    #[test]
+    fn test_request_for_another_component_fails() {
+        let (mut test_engine, secret_key) = instantiate_with_gumball_machine();
+
+        // Another subscriber names the gumball machine as callback of its own request
+        test_engine.set_current_component("morpher oracle");
+        test_engine
+            .call_method(
+                "new_subscription",
+                env_args!(1u64, Fungible::Bucket("XRD", 30)),
+            )
+            .assert_is_success();
+        let ids = test_engine.current_ids_balance("Morpher subscription");
         test_engine
             .call_method(
-                "sell",
+                "request_price",
                 env_args!(
-                    FungibleAll::FromAccount("MPH"),
-                    new_price_message.to_string(),
-                    sign(new_price_message, &secret_key)
+                    "GATEIO:XRD_USDT".to_string(),
+                    Environment::Component("gumball machine"),
+                    "receive_price".to_string(),
+                    NonFungible::Proof("Morpher subscription", ids)
                 ),
             )
             .assert_is_success();
 
-        let xrd_after = test_engine.current_balance("xrd");
+        fulfil(
+            &mut test_engine,
+            0,
+            price_message(dec!(1000), 0, 0),
+            &secret_key,
+        )
+        .assert_failed_with("This price was not requested by this component");
+
+        let status: Status = test_engine
+            .call_method("get_status", env_args!())
+            .expect_commit_success()
+            .output(1);
+        assert_eq!(status.requested_price, None);
+    }
+
+    #[test]
+    fn test_receive_price_only_from_the_oracle() {
+        let (mut test_engine, _) = instantiate_with_gumball_machine();
 
-        assert_eq!(xrd_after - xrd_before, dec!(3));
-        assert_eq!(test_engine.current_balance("mph"), dec!(0))
+        test_engine
+            .call_method(
+                "receive_price",
+                env_args!(0u64, price_message(dec!(1), 0, 0)),
+            )
+            .assert_failed_with("Unauthorized");
     }
 
     #[test]
@@ -405,7 +2910,9 @@ mod trading_test {
             env_args!(
                 secret_key.public_key().to_string(),
                 dec!("30"),
-                Environment::Account("default")
+                RoyaltyAmount::Xrd(dec!("1")),
+                Environment::Account("default"),
+                SUBSCRIPTION_IMAGE_URL.to_string()
             ),
         );
     }